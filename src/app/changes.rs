@@ -0,0 +1,151 @@
+use bevy::prelude::*;
+
+use super::{
+    vlox::{VloxData, VloxPatch, VOID},
+    VloxChanged, VloxSettings,
+};
+
+/// Extension of saved patches, patch files dropped onto the window are applied
+pub const PATCH_EXTENSION: &str = "vxpatch";
+/// Where `CONTROLS_SAVE_PATCH` writes the changes
+const PATCH_SAVE_PATH: &str = "changes.vxpatch";
+
+/// Starts tracking changes from the current state, or stops tracking
+const CONTROLS_TRACK_CHANGES: KeyCode = KeyCode::F4;
+/// With Ctrl held: save the tracked changes as a patch, or revert them
+const CONTROLS_SAVE_PATCH: KeyCode = KeyCode::KeyE;
+const CONTROLS_REVERT_CHANGES: KeyCode = KeyCode::KeyR;
+const CONTROLS_PATCH_MODIFIERS: [KeyCode; 2] = [KeyCode::ControlLeft, KeyCode::ControlRight];
+
+const ADDED_COLOR: Color = Color::srgb(0.3, 1.0, 0.3);
+const REMOVED_COLOR: Color = Color::srgb(1.0, 0.3, 0.3);
+const CHANGED_COLOR: Color = Color::srgb(1.0, 0.6, 0.0);
+
+/// Highlights everything that changed since tracking started, and saves, reverts and
+/// applies the changes as patches.
+///
+/// Saving and dropping patch files don't work in the web build yet, which has no file system
+/// and gets no file drops.
+pub struct ChangesPlugin;
+impl Plugin for ChangesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TrackedChanges>().add_systems(
+            Update,
+            (
+                track_changes,
+                apply_dropped_patch,
+                update_changes,
+                draw_changes,
+            )
+                .chain(),
+        );
+    }
+}
+
+#[derive(Resource, Default)]
+struct TrackedChanges {
+    /// The data when tracking started
+    baseline: Option<VloxData>,
+    /// From the baseline to the current data
+    patch: VloxPatch,
+}
+
+fn track_changes(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut tracked: ResMut<TrackedChanges>,
+    mut vlox_settings: ResMut<VloxSettings>,
+    mut vlox_changed: EventWriter<VloxChanged>,
+) {
+    if keyboard_input.just_pressed(CONTROLS_TRACK_CHANGES) {
+        *tracked = match tracked.baseline {
+            Some(_) => TrackedChanges::default(),
+            None => TrackedChanges {
+                baseline: Some(vlox_settings.data.clone()),
+                patch: VloxPatch::default(),
+            },
+        };
+    }
+    if tracked.baseline.is_none() || !keyboard_input.any_pressed(CONTROLS_PATCH_MODIFIERS) {
+        return;
+    }
+
+    if keyboard_input.just_pressed(CONTROLS_SAVE_PATCH) {
+        match std::fs::write(PATCH_SAVE_PATH, tracked.patch.to_bytes()) {
+            Ok(()) => info!(
+                "saved {} changes to {PATCH_SAVE_PATH}",
+                tracked.patch.changes.len()
+            ),
+            Err(error) => error!("couldn't save changes: {error}"),
+        }
+    }
+    if keyboard_input.just_pressed(CONTROLS_REVERT_CHANGES) && !tracked.patch.is_empty() {
+        vlox_settings.data.apply(&tracked.patch.invert());
+        vlox_changed.send(VloxChanged::All);
+    }
+}
+
+fn apply_dropped_patch(
+    mut drops: EventReader<FileDragAndDrop>,
+    mut vlox_settings: ResMut<VloxSettings>,
+    mut vlox_changed: EventWriter<VloxChanged>,
+) {
+    for drop in drops.read() {
+        let FileDragAndDrop::DroppedFile { path_buf, .. } = drop else {
+            continue;
+        };
+        if path_buf
+            .extension()
+            .and_then(|extension| extension.to_str())
+            != Some(PATCH_EXTENSION)
+        {
+            continue;
+        }
+
+        let patch = std::fs::read(path_buf)
+            .map_err(|error| error.to_string())
+            .and_then(|bytes| VloxPatch::from_bytes(&bytes).map_err(|error| error.to_string()));
+        match patch {
+            Ok(patch) => {
+                vlox_settings.data.apply(&patch);
+                vlox_changed.send(VloxChanged::All);
+                info!("applied patch {}", path_buf.display());
+            }
+            Err(error) => error!("couldn't apply patch {}: {error}", path_buf.display()),
+        }
+    }
+}
+
+fn update_changes(
+    mut changes: EventReader<VloxChanged>,
+    vlox_settings: Res<VloxSettings>,
+    mut tracked: ResMut<TrackedChanges>,
+) {
+    if changes.read().count() == 0 && !tracked.is_changed() {
+        return;
+    }
+    let tracked = &mut *tracked;
+    if let Some(baseline) = &tracked.baseline {
+        tracked.patch = baseline.diff(&vlox_settings.data);
+    }
+}
+
+fn draw_changes(
+    mut gizmos: Gizmos,
+    tracked: Res<TrackedChanges>,
+    vlox_settings: Res<VloxSettings>,
+) {
+    let data = &vlox_settings.data;
+    for change in &tracked.patch.changes {
+        let (x, y, z) = data.vlox_xyz_to_xyz_f32(change.x, change.y, change.z, change.depth);
+        let size = data.vlox_size(data.num_vlox(change.depth));
+        let color = match (change.old_value(), change.new_value()) {
+            (Some(VOID), _) => ADDED_COLOR,
+            (_, Some(VOID)) => REMOVED_COLOR,
+            _ => CHANGED_COLOR,
+        };
+        gizmos.cuboid(
+            Transform::from_xyz(x, y, z).with_scale(Vec3::splat(size)),
+            color,
+        );
+    }
+}
@@ -0,0 +1,78 @@
+use bevy::prelude::*;
+
+use super::{vlox::ClipPlane, VloxSettings};
+
+/// Cycles the clipping plane through off, x, y and z
+const CONTROLS_CYCLE_CLIP: KeyCode = KeyCode::F8;
+/// Move the clipping plane back and forward by one vlox of the selected depth
+const CONTROLS_CLIP_BACK: KeyCode = KeyCode::PageDown;
+const CONTROLS_CLIP_FORWARD: KeyCode = KeyCode::PageUp;
+
+const CLIP_PLANE_COLOR: Color = Color::srgba(1.0, 1.0, 0.3, 0.6);
+
+/// A cross section view: hides everything beyond a movable axis aligned plane
+pub struct ClipPlugin;
+impl Plugin for ClipPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ClipChanged>()
+            .add_systems(Update, (move_clip_plane, draw_clip_plane));
+    }
+}
+
+/// Sent when the clipping plane moves. Only the mesh has to be rebuilt, the data is unchanged.
+#[derive(Event)]
+pub struct ClipChanged;
+
+fn move_clip_plane(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut vlox_settings: ResMut<VloxSettings>,
+    mut clip_changed: EventWriter<ClipChanged>,
+) {
+    let before = vlox_settings.clip;
+    if keyboard_input.just_pressed(CONTROLS_CYCLE_CLIP) {
+        vlox_settings.clip = match vlox_settings.clip {
+            None => Some(ClipPlane {
+                axis: 0,
+                position: 0.0,
+            }),
+            Some(ClipPlane { axis: 2, .. }) => None,
+            Some(clip) => Some(ClipPlane {
+                axis: clip.axis + 1,
+                ..clip
+            }),
+        };
+    }
+
+    let steps = keyboard_input.just_pressed(CONTROLS_CLIP_FORWARD) as i32
+        - keyboard_input.just_pressed(CONTROLS_CLIP_BACK) as i32;
+    let data = &vlox_settings.data;
+    let half = data.size() * 0.5;
+    let step = data.vlox_size(data.num_vlox(vlox_settings.selected_depth));
+    if let Some(clip) = &mut vlox_settings.clip {
+        if steps != 0 {
+            // snap to the selected depth's grid, so the cut falls between vloxes
+            let position = ((clip.position / step).round() + steps as f32) * step;
+            clip.position = position.clamp(-half, half);
+        }
+    }
+
+    if vlox_settings.clip != before {
+        clip_changed.send(ClipChanged);
+    }
+}
+
+fn draw_clip_plane(mut gizmos: Gizmos, vlox_settings: Res<VloxSettings>) {
+    let Some(clip) = vlox_settings.clip else {
+        return;
+    };
+    let (position, rotation) = match clip.axis {
+        0 => (Vec3::X, Quat::from_rotation_y(std::f32::consts::FRAC_PI_2)),
+        1 => (Vec3::Y, Quat::from_rotation_x(std::f32::consts::FRAC_PI_2)),
+        _ => (Vec3::Z, Quat::IDENTITY),
+    };
+    gizmos.rect(
+        Isometry3d::new(position * clip.position, rotation),
+        Vec2::splat(vlox_settings.data.size()),
+        CLIP_PLANE_COLOR,
+    );
+}
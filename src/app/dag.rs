@@ -0,0 +1,63 @@
+use bevy::prelude::*;
+
+use super::{
+    vlox::{DagStats, VloxDag},
+    VloxChanged, VloxSettings,
+};
+
+/// Toggles storing identical subtrees once
+const CONTROLS_TOGGLE_DAG: KeyCode = KeyCode::F5;
+
+/// DAG mode: while enabled, identical subtrees of the data are stored once and shared, so
+/// repetitive builds take less memory, and the HUD shows how much is saved. Edits copy the
+/// shared nodes they change, and the copies are shared again after each change. Turning it
+/// off stops sharing new subtrees, what is already shared stays shared until it is edited.
+pub struct DagPlugin;
+impl Plugin for DagPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DagMode>()
+            .add_systems(Update, (toggle_dag, update_dag).chain());
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct DagMode {
+    dag: Option<VloxDag>,
+    /// Savings after the last edits, None while disabled
+    pub stats: Option<DagStats>,
+}
+
+fn toggle_dag(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut vlox_settings: ResMut<VloxSettings>,
+    mut dag_mode: ResMut<DagMode>,
+) {
+    if !keyboard_input.just_pressed(CONTROLS_TOGGLE_DAG) {
+        return;
+    }
+    if dag_mode.dag.take().is_some() {
+        dag_mode.stats = None;
+        return;
+    }
+    let mut dag = VloxDag::default();
+    vlox_settings.data.share(&mut dag);
+    dag_mode.stats = Some(vlox_settings.data.dag_stats());
+    dag_mode.dag = Some(dag);
+}
+
+/// Shares what the edits copied or added, which is all sharing visits
+fn update_dag(
+    mut changes: EventReader<VloxChanged>,
+    mut vlox_settings: ResMut<VloxSettings>,
+    mut dag_mode: ResMut<DagMode>,
+) {
+    let changed = changes.read().count() > 0;
+    let dag_mode = &mut *dag_mode;
+    let Some(dag) = &mut dag_mode.dag else {
+        return;
+    };
+    if changed {
+        vlox_settings.data.share(dag);
+        dag_mode.stats = Some(vlox_settings.data.dag_stats());
+    }
+}
@@ -0,0 +1,57 @@
+use bevy::prelude::*;
+
+use super::{hotbar::Hotbar, VloxSettings, COMPUTE_MESH_DEPTH, MAX_VLOX_DEPTH, MIN_VLOX_DEPTH};
+
+const CONTROLS_PICK: MouseButton = MouseButton::Middle;
+/// Held with the left mouse button, for mice without a middle button
+const CONTROLS_PICK_MODIFIERS: [KeyCode; 2] = [KeyCode::AltLeft, KeyCode::AltRight];
+/// Held while picking to select the depth the picked vlox is stored at as well
+const CONTROLS_PICK_DEPTH_MODIFIERS: [KeyCode; 2] = [KeyCode::ControlLeft, KeyCode::ControlRight];
+
+/// Picks the material, and optionally the depth, of the vlox under the crosshair
+pub struct EyedropperPlugin;
+impl Plugin for EyedropperPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, pick_vlox);
+    }
+}
+
+/// True while the left mouse button picks instead of using the active tool
+pub fn picking(keyboard_input: &ButtonInput<KeyCode>) -> bool {
+    keyboard_input.any_pressed(CONTROLS_PICK_MODIFIERS)
+}
+
+fn pick_vlox(
+    camera: Single<&Transform, With<Camera>>,
+    mouse_button_input: Res<ButtonInput<MouseButton>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut vlox_settings: ResMut<VloxSettings>,
+    mut hotbar: ResMut<Hotbar>,
+) {
+    let pressed = mouse_button_input.just_pressed(CONTROLS_PICK)
+        || (picking(&keyboard_input) && mouse_button_input.just_pressed(MouseButton::Left));
+    if !pressed {
+        return;
+    }
+
+    let origin = camera.translation;
+    let direction = camera.forward().as_vec3();
+    let Some(hit) = vlox_settings.data.raycast(
+        origin.into(),
+        direction.into(),
+        COMPUTE_MESH_DEPTH,
+        vlox_settings.clip.as_ref(),
+    ) else {
+        return;
+    };
+    let (value, depth) = vlox_settings.data.leaf(hit.x, hit.y, hit.z, hit.depth);
+
+    // the hotbar decides the selected material, so the pick goes through it
+    match hotbar.slots.iter().position(|slot| *slot == Some(value)) {
+        Some(slot) => hotbar.selected = slot,
+        None => hotbar.assign(value),
+    }
+    if keyboard_input.any_pressed(CONTROLS_PICK_DEPTH_MODIFIERS) {
+        vlox_settings.selected_depth = depth.clamp(MIN_VLOX_DEPTH, MAX_VLOX_DEPTH);
+    }
+}
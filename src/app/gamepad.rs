@@ -0,0 +1,163 @@
+use bevy::prelude::*;
+use bevy_flycam::FlyCam;
+
+/// World units moved per second with the left stick pushed all the way
+const GAMEPAD_MOVE_SPEED: f32 = 6.0;
+/// Radians turned per second with the right stick pushed all the way
+const GAMEPAD_LOOK_SPEED: f32 = 2.5;
+/// Stops the camera looking straight up or down, like the fly camera
+const MAX_PITCH: f32 = 1.54;
+
+/// Flies the camera with the sticks, the left one moves and the right one looks around.
+/// Buttons are bound to actions in the input map, so they work alongside the keyboard and mouse.
+pub struct GamepadControlsPlugin;
+impl Plugin for GamepadControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, fly_with_gamepad);
+    }
+}
+
+/// Only moves the fly camera, so the orbit camera and axis views are left alone
+fn fly_with_gamepad(
+    time: Res<Time>,
+    gamepads: Query<&Gamepad>,
+    mut camera: Single<&mut Transform, With<FlyCam>>,
+) {
+    let (movement, look) =
+        gamepads
+            .iter()
+            .fold((Vec2::ZERO, Vec2::ZERO), |(movement, look), gamepad| {
+                (
+                    movement + gamepad.left_stick(),
+                    look + gamepad.right_stick(),
+                )
+            });
+    let delta = time.delta_secs();
+    fly(
+        &mut camera,
+        movement * GAMEPAD_MOVE_SPEED * delta,
+        look * GAMEPAD_LOOK_SPEED * delta,
+    );
+}
+
+/// Turns the camera by `turn` radians of yaw and pitch, then moves it by `movement` along its
+/// right and forward directions, the way the fly camera does
+pub fn fly(camera: &mut Transform, movement: Vec2, turn: Vec2) {
+    if turn != Vec2::ZERO {
+        let (yaw, pitch, _) = camera.rotation.to_euler(EulerRot::YXZ);
+        let yaw = yaw - turn.x;
+        let pitch = (pitch + turn.y).clamp(-MAX_PITCH, MAX_PITCH);
+        camera.rotation =
+            Quat::from_axis_angle(Vec3::Y, yaw) * Quat::from_axis_angle(Vec3::X, pitch);
+    }
+    if movement != Vec2::ZERO {
+        let direction = camera.forward() * movement.y + camera.right() * movement.x;
+        camera.translation += direction;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::{
+        input::gamepad::{
+            GamepadConnection, GamepadConnectionEvent, RawGamepadAxisChangedEvent,
+            RawGamepadButtonChangedEvent, RawGamepadEvent,
+        },
+        time::TimeUpdateStrategy,
+    };
+
+    use super::super::input::{Action, InputMap, InputPlugin};
+    use super::*;
+
+    /// A headless app with the input map and the gamepad controls, and a connected gamepad
+    fn app() -> (App, Entity) {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            bevy::input::InputPlugin,
+            InputPlugin,
+            GamepadControlsPlugin,
+        ))
+        // ignore any input map saved in the working directory
+        .insert_resource(InputMap::default())
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+            100,
+        )));
+        app.world_mut().spawn((Transform::default(), FlyCam));
+
+        let gamepad = app.world_mut().spawn_empty().id();
+        app.world_mut().send_event(GamepadConnectionEvent::new(
+            gamepad,
+            GamepadConnection::Connected {
+                name: "Test gamepad".to_string(),
+                vendor_id: None,
+                product_id: None,
+            },
+        ));
+        app.update();
+        (app, gamepad)
+    }
+
+    /// Sends a button the way a gamepad backend does, 1.0 is fully pressed
+    fn press(app: &mut App, gamepad: Entity, button: GamepadButton, value: f32) {
+        app.world_mut()
+            .send_event(RawGamepadEvent::Button(RawGamepadButtonChangedEvent::new(
+                gamepad, button, value,
+            )));
+    }
+
+    fn tilt(app: &mut App, gamepad: Entity, axis: GamepadAxis, value: f32) {
+        app.world_mut()
+            .send_event(RawGamepadEvent::Axis(RawGamepadAxisChangedEvent::new(
+                gamepad, axis, value,
+            )));
+    }
+
+    fn camera(app: &mut App) -> Transform {
+        *app.world_mut()
+            .query_filtered::<&Transform, With<FlyCam>>()
+            .single(app.world())
+    }
+
+    #[test]
+    fn gamepad_buttons_press_actions() {
+        let (mut app, gamepad) = app();
+        press(&mut app, gamepad, GamepadButton::RightTrigger2, 1.0);
+        press(&mut app, gamepad, GamepadButton::DPadRight, 1.0);
+        app.update();
+
+        let actions = app.world().resource::<ButtonInput<Action>>();
+        assert!(actions.just_pressed(Action::Place));
+        assert!(actions.just_pressed(Action::HotbarNext));
+        assert!(!actions.pressed(Action::Erase));
+
+        press(&mut app, gamepad, GamepadButton::RightTrigger2, 0.0);
+        app.update();
+        let actions = app.world().resource::<ButtonInput<Action>>();
+        assert!(actions.just_released(Action::Place));
+        assert!(actions.pressed(Action::HotbarNext));
+        assert!(!actions.just_pressed(Action::HotbarNext));
+    }
+
+    #[test]
+    fn sticks_fly_the_camera() {
+        let (mut app, gamepad) = app();
+        let start = camera(&mut app);
+        tilt(&mut app, gamepad, GamepadAxis::LeftStickY, 1.0);
+        app.update();
+        let moved = camera(&mut app);
+        // forward is -z
+        assert!(moved.translation.z < start.translation.z);
+        assert_eq!(start.rotation, moved.rotation);
+
+        tilt(&mut app, gamepad, GamepadAxis::LeftStickY, 0.0);
+        tilt(&mut app, gamepad, GamepadAxis::RightStickX, 1.0);
+        app.update();
+        let turned = camera(&mut app);
+        assert_eq!(moved.translation, turned.translation);
+        // pushing right turns clockwise seen from above
+        assert!(turned.forward().x > 0.0);
+    }
+}
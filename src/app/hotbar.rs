@@ -0,0 +1,205 @@
+use bevy::{input::mouse::MouseWheel, prelude::*};
+
+use super::{input::Action, orbit::OrbitCamera, vlox, VloxChanged, VloxSettings};
+
+pub const HOTBAR_SLOTS: usize = 10;
+
+/// Cycle the material assigned to the selected slot through the whole `MaterialMap`
+const CONTROLS_SLOT_MATERIAL_NEXT: KeyCode = KeyCode::BracketRight;
+const CONTROLS_SLOT_MATERIAL_PREV: KeyCode = KeyCode::BracketLeft;
+
+const SLOT_SIZE: f32 = 56.0;
+const SLOT_BORDER: Color = Color::srgba(0.0, 0.0, 0.0, 0.6);
+const SLOT_SELECTED_BORDER: Color = Color::WHITE;
+
+/// Material shortcuts, selected with the hotbar slot actions or the scroll wheel outside orbit
+/// mode.
+/// The selected slot's material is what gets placed.
+pub struct HotbarPlugin;
+impl Plugin for HotbarPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Hotbar>()
+            .add_systems(Startup, spawn_hotbar_ui)
+            .add_systems(
+                Update,
+                (fill_hotbar, select_hotbar_slot, update_hotbar_ui).chain(),
+            );
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Hotbar {
+    pub slots: [Option<vlox::MaterialId>; HOTBAR_SLOTS],
+    pub selected: usize,
+}
+impl Hotbar {
+    pub fn selected_material(&self) -> Option<vlox::MaterialId> {
+        self.slots[self.selected]
+    }
+    /// Puts a material in the selected slot
+    pub fn assign(&mut self, id: vlox::MaterialId) {
+        self.slots[self.selected] = Some(id);
+    }
+}
+
+#[derive(Component)]
+struct HotbarSwatch(usize);
+
+#[derive(Component)]
+struct HotbarLabel(usize);
+
+/// Drops slots whose material no longer exists and fills empty slots with unassigned materials
+fn fill_hotbar(
+    mut changes: EventReader<VloxChanged>,
+    vlox_settings: Res<VloxSettings>,
+    mut hotbar: ResMut<Hotbar>,
+) {
+    if !changes
+        .read()
+        .any(|change| matches!(change, VloxChanged::All))
+    {
+        return;
+    }
+
+    let ids = vlox_settings.materials.ids();
+    for slot in hotbar.slots.iter_mut() {
+        if slot.is_some_and(|id| !ids.contains(&id)) {
+            *slot = None;
+        }
+    }
+    let mut unassigned = ids
+        .into_iter()
+        .filter(|id| !hotbar.slots.contains(&Some(*id)));
+    let mut filled = hotbar.slots;
+    for slot in filled.iter_mut().filter(|slot| slot.is_none()) {
+        *slot = unassigned.next();
+    }
+    hotbar.slots = filled;
+}
+
+fn select_hotbar_slot(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    actions: Res<ButtonInput<Action>>,
+    mut mouse_wheel: EventReader<MouseWheel>,
+    mut hotbar: ResMut<Hotbar>,
+    mut vlox_settings: ResMut<VloxSettings>,
+    orbit: Res<OrbitCamera>,
+) {
+    for slot in 0..HOTBAR_SLOTS {
+        if actions.just_pressed(Action::HotbarSlot(slot)) {
+            hotbar.selected = slot;
+        }
+    }
+
+    let scroll: f32 = mouse_wheel.read().map(|wheel| wheel.y).sum();
+    // the orbit camera zooms with the wheel instead
+    let scroll = if orbit.active { 0.0 } else { scroll };
+    if scroll < 0.0 || actions.just_pressed(Action::HotbarNext) {
+        hotbar.selected = (hotbar.selected + 1) % HOTBAR_SLOTS;
+    } else if scroll > 0.0 || actions.just_pressed(Action::HotbarPrevious) {
+        hotbar.selected = (hotbar.selected + HOTBAR_SLOTS - 1) % HOTBAR_SLOTS;
+    }
+
+    let step = match (
+        keyboard_input.just_pressed(CONTROLS_SLOT_MATERIAL_NEXT),
+        keyboard_input.just_pressed(CONTROLS_SLOT_MATERIAL_PREV),
+    ) {
+        (true, false) => Some(1),
+        (false, true) => Some(-1),
+        _ => None,
+    };
+    if let Some(step) = step {
+        let ids = vlox_settings.materials.ids();
+        if !ids.is_empty() {
+            let current = hotbar
+                .selected_material()
+                .and_then(|id| ids.iter().position(|other| *other == id));
+            let next = match current {
+                Some(i) => (i as isize + step).rem_euclid(ids.len() as isize) as usize,
+                None => 0,
+            };
+            hotbar.assign(ids[next]);
+        }
+    }
+
+    if let Some(id) = hotbar.selected_material() {
+        if vlox_settings.selected_value != id {
+            vlox_settings.selected_value = id;
+        }
+    }
+}
+
+fn spawn_hotbar_ui(mut commands: Commands) {
+    commands
+        .spawn(Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(12.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            column_gap: Val::Px(4.0),
+            ..default()
+        })
+        .with_children(|hotbar| {
+            for slot in 0..HOTBAR_SLOTS {
+                hotbar
+                    .spawn((
+                        Node {
+                            width: Val::Px(SLOT_SIZE),
+                            height: Val::Px(SLOT_SIZE),
+                            border: UiRect::all(Val::Px(3.0)),
+                            flex_direction: FlexDirection::Column,
+                            justify_content: JustifyContent::SpaceBetween,
+                            padding: UiRect::all(Val::Px(2.0)),
+                            ..default()
+                        },
+                        BorderColor(SLOT_BORDER),
+                        BackgroundColor(Color::NONE),
+                        HotbarSwatch(slot),
+                    ))
+                    .with_children(|swatch| {
+                        swatch.spawn((
+                            Text::new(format!("{}", (slot + 1) % HOTBAR_SLOTS)),
+                            TextFont::from_font_size(11.0),
+                        ));
+                        swatch.spawn((
+                            Text::new(""),
+                            TextFont::from_font_size(10.0),
+                            HotbarLabel(slot),
+                        ));
+                    });
+            }
+        });
+}
+
+fn update_hotbar_ui(
+    hotbar: Res<Hotbar>,
+    vlox_settings: Res<VloxSettings>,
+    mut swatches: Query<(&HotbarSwatch, &mut BorderColor, &mut BackgroundColor)>,
+    mut labels: Query<(&HotbarLabel, &mut Text)>,
+) {
+    if !hotbar.is_changed() {
+        return;
+    }
+
+    for (HotbarSwatch(slot), mut border, mut background) in swatches.iter_mut() {
+        border.0 = if *slot == hotbar.selected {
+            SLOT_SELECTED_BORDER
+        } else {
+            SLOT_BORDER
+        };
+        background.0 =
+            match hotbar.slots[*slot].map(|id| vlox_settings.materials.color(id, 0, 0, 0, 0)) {
+                Some(vlox::VloxColor::Solid(color)) => {
+                    let [r, g, b, a] = color.as_f32x4();
+                    Color::linear_rgba(r, g, b, a)
+                }
+                _ => Color::NONE,
+            };
+    }
+    for (HotbarLabel(slot), mut text) in labels.iter_mut() {
+        text.0 = hotbar.slots[*slot]
+            .and_then(|id| vlox_settings.materials.name(id))
+            .unwrap_or_default()
+            .to_string();
+    }
+}
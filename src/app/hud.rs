@@ -0,0 +1,162 @@
+use bevy::{
+    diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin},
+    prelude::*,
+};
+
+use super::{
+    dag::DagMode, octree::OctreeOverlay, tools::ToolRegistry, MainMesh, VloxChanged, VloxSettings,
+};
+
+const CONTROLS_TOGGLE_HUD: KeyCode = KeyCode::F3;
+
+/// On-screen overlay with the editor state, toggled with F3
+pub struct HudPlugin;
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(FrameTimeDiagnosticsPlugin)
+            .init_resource::<NodeCount>()
+            .add_systems(Startup, spawn_hud)
+            .add_systems(Update, (toggle_hud, (count_nodes, update_hud).chain()));
+    }
+}
+
+#[derive(Component)]
+struct Hud;
+
+/// Nodes stored in the tree, None while the HUD is hidden
+#[derive(Resource, Default)]
+struct NodeCount(Option<usize>);
+
+fn spawn_hud(mut commands: Commands) {
+    commands.spawn((
+        Text::default(),
+        TextFont::from_font_size(14.0),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(8.0),
+            left: Val::Px(8.0),
+            padding: UiRect::all(Val::Px(6.0)),
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.5)),
+        Hud,
+    ));
+}
+
+fn toggle_hud(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut hud: Single<&mut Visibility, With<Hud>>,
+) {
+    if keyboard_input.just_pressed(CONTROLS_TOGGLE_HUD) {
+        hud.toggle_visible_hidden();
+    }
+}
+
+/// Counting walks the whole tree, so it is only redone when the data changes
+fn count_nodes(
+    mut changes: EventReader<VloxChanged>,
+    hud: Single<&Visibility, With<Hud>>,
+    vlox_settings: Res<VloxSettings>,
+    mut count: ResMut<NodeCount>,
+) {
+    let changed = changes.read().count() > 0;
+    if **hud == Visibility::Hidden {
+        count.0 = None;
+    } else if changed || count.0.is_none() {
+        count.0 = Some(vlox_settings.data.node_count());
+    }
+}
+
+fn update_hud(
+    mut hud: Single<(&mut Text, &Visibility), With<Hud>>,
+    vlox_settings: Res<VloxSettings>,
+    tools: Res<ToolRegistry>,
+    diagnostics: Res<DiagnosticsStore>,
+    main_mesh: Single<&Mesh3d, With<MainMesh>>,
+    meshes: Res<Assets<Mesh>>,
+    (dag_mode, octree, nodes): (Res<DagMode>, Res<OctreeOverlay>, Res<NodeCount>),
+) {
+    if hud.1 == Visibility::Hidden {
+        return;
+    }
+
+    let depth = vlox_settings.selected_depth;
+    let size = vlox_settings
+        .data
+        .vlox_size(vlox_settings.data.num_vlox(depth));
+    let material = vlox_settings
+        .materials
+        .name(vlox_settings.selected_value)
+        .unwrap_or("-");
+    let tool = match tools.active() {
+        Some(tool) => match tool.mode() {
+            Some(mode) => format!("{} ({mode})", tool.name()),
+            None => tool.name().to_string(),
+        },
+        None => "-".to_string(),
+    };
+    let mirror: String = ["X", "Y", "Z"]
+        .into_iter()
+        .zip(vlox_settings.mirror.planes)
+        .filter(|(_, plane)| plane.is_some())
+        .map(|(axis, _)| axis)
+        .collect::<Vec<_>>()
+        .join(" ");
+    let hovered = match vlox_settings.hovered {
+        Some((x, y, z)) => {
+            let summary = vlox_settings.data.summary(x, y, z, depth);
+            format!(
+                "{x}, {y}, {z} ({} {:.0}%)",
+                vlox_settings
+                    .materials
+                    .name(summary.material)
+                    .unwrap_or("-"),
+                summary.occupancy * 100.0
+            )
+        }
+        None => "-".to_string(),
+    };
+    let triangles = meshes
+        .get(&main_mesh.0)
+        .and_then(|mesh| mesh.indices())
+        .map_or(0, |indices| indices.len() / 3);
+    let dag = match dag_mode.stats {
+        Some(stats) => format!(
+            "\nDAG: {} of {} nodes, saves {} KiB",
+            stats.unique_nodes,
+            stats.tree_nodes,
+            stats.saved_bytes() / 1024
+        ),
+        None => String::new(),
+    };
+    let octree: String = octree
+        .counts
+        .iter()
+        .enumerate()
+        .map(|(depth, (nodes, leaves))| {
+            format!("\n  Depth {depth}: {nodes} nodes, {leaves} leaves")
+        })
+        .collect();
+    let fps = diagnostics
+        .get(&FrameTimeDiagnosticsPlugin::FPS)
+        .and_then(|fps| fps.smoothed())
+        .unwrap_or(0.0);
+
+    hud.0 .0 = format!(
+        "Tool: {tool}\nDepth: {depth} ({})\nMaterial: {material}\nMirror: {}\nHovered: {hovered}\nNodes: {}{octree}{dag}\nTriangles: {triangles}\nFPS: {fps:.0}",
+        real_world_size(size),
+        if mirror.is_empty() { "-" } else { &mirror },
+        nodes.0.unwrap_or_default(),
+    );
+}
+
+/// World units are metres
+fn real_world_size(size: f32) -> String {
+    if size >= 1.0 {
+        format!("{size} m")
+    } else if size >= 0.01 {
+        format!("{} cm", size * 100.0)
+    } else {
+        format!("{} mm", size * 1000.0)
+    }
+}
@@ -0,0 +1,516 @@
+use bevy::{input::InputSystem, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use super::hotbar::HOTBAR_SLOTS;
+
+/// Where the input map is loaded from at startup, and saved to after rebinding. The web build
+/// has no file system, so it keeps the map in local storage under this key instead.
+const INPUT_MAP_PATH: &str = "input.ron";
+
+/// Opens and closes the rebinding screen
+const CONTROLS_TOGGLE_REBINDING: KeyCode = KeyCode::F9;
+/// Keys used inside the rebinding screen, which can't be rebound themselves
+const CONTROLS_REBINDING_UP: KeyCode = KeyCode::ArrowUp;
+const CONTROLS_REBINDING_DOWN: KeyCode = KeyCode::ArrowDown;
+/// Waits for the next button and binds it instead of the current bindings, with Shift as well
+/// as them
+const CONTROLS_REBINDING_BIND: KeyCode = KeyCode::Enter;
+const CONTROLS_REBINDING_ADD_MODIFIERS: [KeyCode; 2] = [KeyCode::ShiftLeft, KeyCode::ShiftRight];
+const CONTROLS_REBINDING_CLEAR: KeyCode = KeyCode::Backspace;
+const CONTROLS_REBINDING_CANCEL: KeyCode = KeyCode::Escape;
+/// Puts every action back to its default bindings
+const CONTROLS_REBINDING_RESET: KeyCode = KeyCode::Home;
+
+/// Maps keys, mouse buttons and gamepad buttons to editor actions, which other plugins read
+/// from `ButtonInput<Action>` instead of the raw inputs
+pub struct InputPlugin;
+impl Plugin for InputPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(InputMap::load())
+            .init_resource::<ButtonInput<Action>>()
+            .init_resource::<Rebinding>()
+            .add_systems(Startup, spawn_rebinding_ui)
+            .add_systems(
+                PreUpdate,
+                (update_actions, rebind)
+                    .chain()
+                    .in_set(UpdateActions)
+                    .after(InputSystem),
+            )
+            .add_systems(Update, update_rebinding_ui);
+    }
+}
+
+/// Where `ButtonInput<Action>` is updated from the bindings. Systems that press actions
+/// themselves, like on-screen buttons, run after it.
+#[derive(SystemSet, Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct UpdateActions;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Action {
+    /// Uses the active tool, placing with the place tool
+    Place,
+    /// Uses the active tool's secondary action, erasing with the place tool
+    Erase,
+    /// Selects larger vloxes, one depth up
+    VloxSizeUp,
+    /// Selects smaller vloxes, one depth down
+    VloxSizeDown,
+    /// Points the camera at the object
+    Focus,
+    HotbarSlot(usize),
+    /// Selects the hotbar slot to the right, like scrolling down
+    HotbarNext,
+    HotbarPrevious,
+    NextTool,
+    PreviousTool,
+    /// Switches the active tool's mode
+    ToolMode,
+}
+
+/// Anything that can be pressed
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Gamepad(GamepadButton),
+}
+impl Binding {
+    fn pressed(
+        &self,
+        keys: &ButtonInput<KeyCode>,
+        mouse: &ButtonInput<MouseButton>,
+        gamepads: &Query<&Gamepad>,
+    ) -> bool {
+        match *self {
+            Binding::Key(key) => keys.pressed(key),
+            Binding::Mouse(button) => mouse.pressed(button),
+            Binding::Gamepad(button) => gamepads.iter().any(|gamepad| gamepad.pressed(button)),
+        }
+    }
+    /// The first binding pressed this frame
+    fn just_pressed(
+        keys: &ButtonInput<KeyCode>,
+        mouse: &ButtonInput<MouseButton>,
+        gamepads: &Query<&Gamepad>,
+    ) -> Option<Self> {
+        keys.get_just_pressed()
+            .next()
+            .map(|key| Binding::Key(*key))
+            .or_else(|| {
+                mouse
+                    .get_just_pressed()
+                    .next()
+                    .map(|button| Binding::Mouse(*button))
+            })
+            .or_else(|| {
+                gamepads
+                    .iter()
+                    .find_map(|gamepad| gamepad.get_just_pressed().next())
+                    .map(|button| Binding::Gamepad(*button))
+            })
+    }
+}
+
+/// The bindings of every action, in the order the rebinding screen lists them
+#[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct InputMap {
+    pub bindings: Vec<(Action, Vec<Binding>)>,
+}
+impl Default for InputMap {
+    fn default() -> Self {
+        let mut bindings = vec![
+            (
+                Action::Place,
+                vec![
+                    Binding::Mouse(MouseButton::Left),
+                    Binding::Gamepad(GamepadButton::RightTrigger2),
+                ],
+            ),
+            (
+                Action::Erase,
+                vec![
+                    Binding::Mouse(MouseButton::Right),
+                    Binding::Gamepad(GamepadButton::LeftTrigger2),
+                ],
+            ),
+            (
+                Action::VloxSizeUp,
+                vec![
+                    Binding::Key(KeyCode::Equal),
+                    Binding::Gamepad(GamepadButton::LeftTrigger),
+                ],
+            ),
+            (
+                Action::VloxSizeDown,
+                vec![
+                    Binding::Key(KeyCode::Minus),
+                    Binding::Gamepad(GamepadButton::RightTrigger),
+                ],
+            ),
+            (Action::Focus, vec![Binding::Key(KeyCode::KeyF)]),
+        ];
+        let digits = [
+            KeyCode::Digit1,
+            KeyCode::Digit2,
+            KeyCode::Digit3,
+            KeyCode::Digit4,
+            KeyCode::Digit5,
+            KeyCode::Digit6,
+            KeyCode::Digit7,
+            KeyCode::Digit8,
+            KeyCode::Digit9,
+            KeyCode::Digit0,
+        ];
+        for (slot, key) in digits.into_iter().enumerate().take(HOTBAR_SLOTS) {
+            bindings.push((Action::HotbarSlot(slot), vec![Binding::Key(key)]));
+        }
+        bindings.extend([
+            (
+                Action::HotbarNext,
+                vec![Binding::Gamepad(GamepadButton::DPadRight)],
+            ),
+            (
+                Action::HotbarPrevious,
+                vec![Binding::Gamepad(GamepadButton::DPadLeft)],
+            ),
+            (
+                Action::NextTool,
+                vec![
+                    Binding::Key(KeyCode::KeyQ),
+                    Binding::Gamepad(GamepadButton::DPadDown),
+                ],
+            ),
+            (
+                Action::PreviousTool,
+                vec![Binding::Gamepad(GamepadButton::DPadUp)],
+            ),
+            (
+                Action::ToolMode,
+                vec![
+                    Binding::Key(KeyCode::Tab),
+                    Binding::Gamepad(GamepadButton::West),
+                ],
+            ),
+        ]);
+        Self { bindings }
+    }
+}
+impl InputMap {
+    /// The saved map, or the defaults if there isn't one
+    fn load() -> Self {
+        let mut map = Self::default();
+        match read_saved_map() {
+            Ok(Some(text)) => match map.merge(&text) {
+                Ok(()) => info!("loaded input map {INPUT_MAP_PATH}"),
+                Err(error) => error!("couldn't load input map {INPUT_MAP_PATH}: {error}"),
+            },
+            Ok(None) => {}
+            Err(error) => error!("couldn't read {INPUT_MAP_PATH}: {error}"),
+        }
+        map
+    }
+    /// Takes the bindings of every action in a saved map. Actions it doesn't mention keep
+    /// their bindings, so actions added since it was saved keep their defaults.
+    fn merge(&mut self, text: &str) -> Result<(), ron::error::SpannedError> {
+        let loaded = ron::from_str::<InputMap>(text)?;
+        for (action, bindings) in loaded.bindings {
+            self.set(action, bindings);
+        }
+        Ok(())
+    }
+    fn save(&self) {
+        let saved = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|error| error.to_string())
+            .and_then(|text| write_saved_map(&text));
+        match saved {
+            Ok(()) => info!("saved input map to {INPUT_MAP_PATH}"),
+            Err(error) => error!("couldn't save input map: {error}"),
+        }
+    }
+    /// Whether anything is bound to `binding`
+    pub fn is_bound(&self, binding: Binding) -> bool {
+        self.bindings
+            .iter()
+            .any(|(_, bindings)| bindings.contains(&binding))
+    }
+    fn set(&mut self, action: Action, bindings: Vec<Binding>) {
+        match self.bindings.iter_mut().find(|(a, _)| *a == action) {
+            Some((_, old)) => *old = bindings,
+            None => self.bindings.push((action, bindings)),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_saved_map() -> Result<Option<String>, String> {
+    match std::fs::read_to_string(INPUT_MAP_PATH) {
+        Ok(text) => Ok(Some(text)),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error.to_string()),
+    }
+}
+#[cfg(not(target_arch = "wasm32"))]
+fn write_saved_map(text: &str) -> Result<(), String> {
+    std::fs::write(INPUT_MAP_PATH, text).map_err(|error| error.to_string())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Result<web_sys::Storage, String> {
+    web_sys::window()
+        .ok_or("no window")?
+        .local_storage()
+        .map_err(|error| format!("{error:?}"))?
+        .ok_or_else(|| "no local storage".to_string())
+}
+#[cfg(target_arch = "wasm32")]
+fn read_saved_map() -> Result<Option<String>, String> {
+    local_storage()?
+        .get_item(INPUT_MAP_PATH)
+        .map_err(|error| format!("{error:?}"))
+}
+#[cfg(target_arch = "wasm32")]
+fn write_saved_map(text: &str) -> Result<(), String> {
+    local_storage()?
+        .set_item(INPUT_MAP_PATH, text)
+        .map_err(|error| format!("{error:?}"))
+}
+
+/// Presses and releases actions to match their bindings. Nothing is pressed while the
+/// rebinding screen is open, so binding a button doesn't also use it.
+fn update_actions(
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    gamepads: Query<&Gamepad>,
+    map: Res<InputMap>,
+    rebinding: Res<Rebinding>,
+    mut actions: ResMut<ButtonInput<Action>>,
+) {
+    actions.clear();
+    for (action, bindings) in &map.bindings {
+        let pressed = !rebinding.open
+            && bindings
+                .iter()
+                .any(|binding| binding.pressed(&keys, &mouse, &gamepads));
+        if pressed {
+            actions.press(*action);
+        } else {
+            actions.release(*action);
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+struct Rebinding {
+    open: bool,
+    /// Index into `InputMap::bindings`
+    selected: usize,
+    /// Waiting for a button to bind, and whether it is added to the existing bindings
+    listening: Option<bool>,
+}
+
+/// Runs before everything else reads the keyboard and mouse, and uses them up while the
+/// screen is open, so keys that aren't actions, like the tools' keys, don't fire either
+fn rebind(
+    mut keys: ResMut<ButtonInput<KeyCode>>,
+    mut mouse: ResMut<ButtonInput<MouseButton>>,
+    gamepads: Query<&Gamepad>,
+    mut map: ResMut<InputMap>,
+    mut rebinding: ResMut<Rebinding>,
+) {
+    if rebinding.listening.is_none() && keys.just_pressed(CONTROLS_TOGGLE_REBINDING) {
+        rebinding.open = !rebinding.open;
+    } else if rebinding.open {
+        rebind_selected(&keys, &mouse, &gamepads, &mut map, &mut rebinding);
+    }
+    if rebinding.open {
+        keys.reset_all();
+        mouse.reset_all();
+    }
+}
+
+fn rebind_selected(
+    keys: &ButtonInput<KeyCode>,
+    mouse: &ButtonInput<MouseButton>,
+    gamepads: &Query<&Gamepad>,
+    map: &mut InputMap,
+    rebinding: &mut Rebinding,
+) {
+    let selected = rebinding.selected;
+    if let Some(add) = rebinding.listening {
+        if keys.just_pressed(CONTROLS_REBINDING_CANCEL) {
+            rebinding.listening = None;
+        } else if let Some(binding) = Binding::just_pressed(keys, mouse, gamepads) {
+            let (_, bindings) = &mut map.bindings[selected];
+            if !add {
+                bindings.clear();
+            }
+            if !bindings.contains(&binding) {
+                bindings.push(binding);
+            }
+            rebinding.listening = None;
+            map.save();
+        }
+        return;
+    }
+
+    let count = map.bindings.len();
+    if keys.just_pressed(CONTROLS_REBINDING_UP) {
+        rebinding.selected = (selected + count - 1) % count;
+    }
+    if keys.just_pressed(CONTROLS_REBINDING_DOWN) {
+        rebinding.selected = (selected + 1) % count;
+    }
+    if keys.just_pressed(CONTROLS_REBINDING_BIND) {
+        rebinding.listening = Some(keys.any_pressed(CONTROLS_REBINDING_ADD_MODIFIERS));
+    }
+    if keys.just_pressed(CONTROLS_REBINDING_CLEAR) {
+        map.bindings[selected].1.clear();
+        map.save();
+    }
+    if keys.just_pressed(CONTROLS_REBINDING_RESET) {
+        *map = InputMap::default();
+        map.save();
+    }
+}
+
+#[derive(Component)]
+struct RebindingScreen;
+
+fn spawn_rebinding_ui(mut commands: Commands) {
+    commands.spawn((
+        Text::default(),
+        TextFont::from_font_size(14.0),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(8.0),
+            right: Val::Px(8.0),
+            padding: UiRect::all(Val::Px(6.0)),
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.7)),
+        Visibility::Hidden,
+        RebindingScreen,
+    ));
+}
+
+fn update_rebinding_ui(
+    mut screen: Single<(&mut Text, &mut Visibility), With<RebindingScreen>>,
+    map: Res<InputMap>,
+    rebinding: Res<Rebinding>,
+) {
+    let (text, visibility) = &mut *screen;
+    **visibility = if rebinding.open {
+        Visibility::Visible
+    } else {
+        Visibility::Hidden
+    };
+    if !rebinding.open || !(map.is_changed() || rebinding.is_changed()) {
+        return;
+    }
+
+    let mut lines = vec![
+        "Controls: Up/Down select, Enter rebind, Shift+Enter add, Backspace clear, \
+         Home reset all"
+            .to_string(),
+    ];
+    for (i, (action, bindings)) in map.bindings.iter().enumerate() {
+        let bound = if i == rebinding.selected && rebinding.listening.is_some() {
+            "press a button, Escape to cancel".to_string()
+        } else if bindings.is_empty() {
+            "-".to_string()
+        } else {
+            bindings
+                .iter()
+                .map(|binding| match binding {
+                    Binding::Key(key) => format!("{key:?}"),
+                    Binding::Mouse(button) => format!("Mouse {button:?}"),
+                    Binding::Gamepad(button) => format!("Gamepad {button:?}"),
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let marker = if i == rebinding.selected { ">" } else { " " };
+        lines.push(format!("{marker} {action:?}: {bound}"));
+    }
+    text.0 = lines.join("\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::input::{keyboard::KeyboardInput, ButtonState};
+
+    use super::*;
+
+    #[test]
+    fn saved_maps_keep_defaults_for_missing_actions() {
+        let mut map = InputMap::default();
+        map.merge("(bindings: [(Focus, [Key(KeyG)]), (HotbarSlot(2), [])])")
+            .unwrap();
+        let bindings = |action| {
+            map.bindings
+                .iter()
+                .find(|(a, _)| *a == action)
+                .map(|(_, bindings)| bindings.clone())
+        };
+        assert_eq!(
+            Some(vec![Binding::Key(KeyCode::KeyG)]),
+            bindings(Action::Focus)
+        );
+        assert_eq!(Some(vec![]), bindings(Action::HotbarSlot(2)));
+        assert_eq!(
+            InputMap::default().bindings[0],
+            (Action::Place, bindings(Action::Place).unwrap())
+        );
+        assert_eq!(InputMap::default().bindings.len(), map.bindings.len());
+
+        assert!(map.merge("(bindings: [(Jump, [])])").is_err());
+    }
+
+    #[test]
+    fn input_maps_round_trip_through_ron() {
+        let text = ron::to_string(&Action::HotbarSlot(7)).unwrap();
+        assert_eq!(Action::HotbarSlot(7), ron::from_str(&text).unwrap());
+
+        let mut map = InputMap::default();
+        map.set(
+            Action::HotbarSlot(3),
+            vec![
+                Binding::Key(KeyCode::KeyH),
+                Binding::Gamepad(GamepadButton::North),
+            ],
+        );
+        let text = ron::ser::to_string_pretty(&map, ron::ser::PrettyConfig::default()).unwrap();
+        let mut loaded = InputMap { bindings: vec![] };
+        loaded.merge(&text).unwrap();
+        assert_eq!(map, loaded);
+    }
+
+    #[test]
+    fn keys_are_used_up_while_rebinding() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, bevy::input::InputPlugin, InputPlugin))
+            // ignore any input map saved in the working directory
+            .insert_resource(InputMap::default());
+        let press = |app: &mut App, key_code| {
+            app.world_mut().send_event(KeyboardInput {
+                key_code,
+                logical_key: bevy::input::keyboard::Key::Unidentified(
+                    bevy::input::keyboard::NativeKey::Unidentified,
+                ),
+                state: ButtonState::Pressed,
+                repeat: false,
+                window: Entity::PLACEHOLDER,
+            });
+            app.update();
+            app.world()
+                .resource::<ButtonInput<KeyCode>>()
+                .pressed(key_code)
+        };
+
+        assert!(press(&mut app, KeyCode::KeyE));
+        assert!(!press(&mut app, CONTROLS_TOGGLE_REBINDING));
+        assert!(app.world().resource::<Rebinding>().open);
+        assert!(!press(&mut app, KeyCode::KeyG));
+    }
+}
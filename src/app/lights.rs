@@ -0,0 +1,100 @@
+use bevy::prelude::*;
+
+use super::{vlox, VloxChanged, VloxSettings, COMPUTE_MESH_DEPTH};
+
+/// Most point lights spawned for emissive vloxes, the nearest clusters to the camera win
+const MAX_VLOX_LIGHTS: usize = 8;
+const LUMENS_PER_EMISSIVE: f32 = 100_000.0;
+const MAX_VLOX_LIGHT_LUMENS: f32 = 4_000_000.0;
+const VLOX_LIGHT_RANGE: f32 = 20.0;
+
+/// Lights emissive vlox clusters (lamps, lava, ...) with real point lights
+pub struct VloxLightsPlugin;
+impl Plugin for VloxLightsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EmissiveClusters>()
+            .add_systems(Startup, spawn_vlox_lights)
+            .add_systems(
+                Update,
+                (update_emissive_clusters, place_vlox_lights).chain(),
+            );
+    }
+}
+
+#[derive(Resource, Default)]
+struct EmissiveClusters(Vec<vlox::EmissiveCluster>);
+
+#[derive(Component)]
+struct VloxLight;
+
+type VloxLightQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static mut PointLight,
+        &'static mut Transform,
+        &'static mut Visibility,
+    ),
+    (With<VloxLight>, Without<Camera>),
+>;
+
+fn spawn_vlox_lights(mut commands: Commands) {
+    for _ in 0..MAX_VLOX_LIGHTS {
+        commands.spawn((
+            PointLight {
+                range: VLOX_LIGHT_RANGE,
+                ..default()
+            },
+            Transform::default(),
+            Visibility::Hidden,
+            VloxLight,
+        ));
+    }
+}
+
+fn update_emissive_clusters(
+    mut changes: EventReader<VloxChanged>,
+    vlox_settings: Res<VloxSettings>,
+    mut clusters: ResMut<EmissiveClusters>,
+) {
+    if changes.read().count() == 0 {
+        return;
+    }
+    clusters.0 = vlox_settings
+        .data
+        .emissive_clusters(COMPUTE_MESH_DEPTH, &vlox_settings.materials);
+}
+
+fn place_vlox_lights(
+    clusters: Res<EmissiveClusters>,
+    camera: Single<Ref<Transform>, With<Camera>>,
+    mut lights: VloxLightQuery,
+) {
+    if !clusters.is_changed() && !camera.is_changed() {
+        return;
+    }
+
+    let camera = camera.translation;
+    let mut nearest: Vec<(f32, &vlox::EmissiveCluster)> = clusters
+        .0
+        .iter()
+        .map(|cluster| {
+            let (x, y, z) = cluster.center;
+            (camera.distance_squared(Vec3::new(x, y, z)), cluster)
+        })
+        .collect();
+    nearest.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    for (i, (mut light, mut transform, mut visibility)) in lights.iter_mut().enumerate() {
+        if let Some((_, cluster)) = nearest.get(i) {
+            let [r, g, b, _] = cluster.color.as_f32x4();
+            let (x, y, z) = cluster.center;
+            light.color = Color::linear_rgb(r, g, b);
+            light.intensity = (cluster.strength * LUMENS_PER_EMISSIVE).min(MAX_VLOX_LIGHT_LUMENS);
+            transform.translation = Vec3::new(x, y, z);
+            *visibility = Visibility::Visible;
+        } else {
+            *visibility = Visibility::Hidden;
+        }
+    }
+}
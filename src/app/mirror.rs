@@ -0,0 +1,276 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+
+use super::{
+    tools::{Cell, ToolPreview, VloxEdit},
+    vlox::VloxData,
+    VloxSettings,
+};
+
+/// Ctrl and an axis key toggle mirroring through the centre, with Shift as well the mirror
+/// plane goes through the hovered vlox instead
+const CONTROLS_MIRROR_AXES: [KeyCode; 3] = [KeyCode::KeyX, KeyCode::KeyY, KeyCode::KeyZ];
+const CONTROLS_MIRROR_MODIFIERS: [KeyCode; 2] = [KeyCode::ControlLeft, KeyCode::ControlRight];
+const CONTROLS_MIRROR_HOVERED_MODIFIERS: [KeyCode; 2] = [KeyCode::ShiftLeft, KeyCode::ShiftRight];
+
+const MIRROR_PLANE_COLORS: [Color; 3] = [
+    Color::srgba(1.0, 0.3, 0.3, 0.6),
+    Color::srgba(0.3, 1.0, 0.3, 0.6),
+    Color::srgba(0.3, 0.3, 1.0, 0.6),
+];
+
+/// Toggles the mirror planes and draws the enabled ones
+pub struct MirrorPlugin;
+impl Plugin for MirrorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (toggle_mirror, draw_mirror_planes));
+    }
+}
+
+/// Mirror planes for symmetric editing, one per axis. Edits are copied across every enabled
+/// plane, and across combinations of them, so all three give eight copies.
+#[derive(Default)]
+pub struct Mirror {
+    /// World position of the plane on each axis, None if the axis isn't mirrored
+    pub planes: [Option<f32>; 3],
+}
+impl Mirror {
+    /// The edits together with all their mirror images, leaving out images outside the data
+    /// and images of vloxes that are already edited. The edits themselves are all kept.
+    pub fn edits(&self, data: &VloxData, edits: Vec<VloxEdit>) -> Vec<VloxEdit> {
+        if self.planes.iter().all(Option::is_none) {
+            return edits;
+        }
+        let mut mirrored = Vec::with_capacity(edits.len());
+        let mut edited = HashSet::new();
+        for edit in edits {
+            // global edits are the same wherever they were picked
+            if edit.op.is_global() {
+                mirrored.push(edit);
+                continue;
+            }
+            edited.insert((edit.cell, edit.depth));
+            mirrored.push(edit);
+            for cell in self.cells(data, edit.cell, edit.depth).into_iter().skip(1) {
+                if edited.insert((cell, edit.depth)) {
+                    mirrored.push(VloxEdit { cell, ..edit });
+                }
+            }
+        }
+        mirrored
+    }
+
+    /// The previews together with all their mirror images
+    pub fn previews(
+        &self,
+        data: &VloxData,
+        depth: u8,
+        previews: Vec<ToolPreview>,
+    ) -> Vec<ToolPreview> {
+        let mut mirrored = vec![];
+        for preview in previews {
+            match preview {
+                ToolPreview::Cells { min, max } => {
+                    let mins = self.images(data, min, depth);
+                    let maxs = self.images(data, max, depth);
+                    // images of the corners of a box are the corners of its image
+                    for (a, b) in mins.into_iter().zip(maxs) {
+                        let (Some(a), Some(b)) = (a, b) else {
+                            continue;
+                        };
+                        mirrored.push(ToolPreview::Cells {
+                            min: (a.0.min(b.0), a.1.min(b.1), a.2.min(b.2)),
+                            max: (a.0.max(b.0), a.1.max(b.1), a.2.max(b.2)),
+                        });
+                    }
+                }
+                ToolPreview::Sphere { center, radius } => {
+                    for center in self.cells(data, center, depth) {
+                        mirrored.push(ToolPreview::Sphere { center, radius });
+                    }
+                }
+            }
+        }
+        mirrored
+    }
+
+    /// A cell and its mirror images at `depth`, starting with the cell itself
+    fn cells(&self, data: &VloxData, cell: Cell, depth: u8) -> Vec<Cell> {
+        self.images(data, cell, depth)
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    /// Like `cells`, but keeps a None for each image outside the data, so the images of
+    /// different cells line up with each other
+    fn images(&self, data: &VloxData, cell: Cell, depth: u8) -> Vec<Option<Cell>> {
+        let mut cells = vec![Some(cell)];
+        for (axis, plane) in self.planes.iter().enumerate() {
+            let Some(plane) = *plane else {
+                continue;
+            };
+            let images: Vec<Option<Cell>> = cells
+                .iter()
+                .map(|cell| {
+                    let mut cell = (*cell)?;
+                    let value = match axis {
+                        0 => &mut cell.0,
+                        1 => &mut cell.1,
+                        _ => &mut cell.2,
+                    };
+                    *value = mirror_index(data, *value, depth, plane)?;
+                    Some(cell)
+                })
+                .collect();
+            cells.extend(images);
+        }
+        cells
+    }
+}
+
+/// The vlox index on one axis mirrored through a plane at world position `plane`
+fn mirror_index(data: &VloxData, index: u128, depth: u8, plane: f32) -> Option<u128> {
+    let num_vlox = data.num_vlox(depth);
+    let vlox_size = data.vlox_size(num_vlox);
+    let center = (index as f32 + 0.5) * vlox_size - data.size() * 0.5;
+    let image = ((2.0 * plane - center + data.size() * 0.5) / vlox_size - 0.5).round();
+    (image >= 0.0 && image < num_vlox as f32).then_some(image as u128)
+}
+
+fn toggle_mirror(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut vlox_settings: ResMut<VloxSettings>,
+) {
+    if !keyboard_input.any_pressed(CONTROLS_MIRROR_MODIFIERS) {
+        return;
+    }
+    let through_hovered = keyboard_input.any_pressed(CONTROLS_MIRROR_HOVERED_MODIFIERS);
+    let depth = vlox_settings.selected_depth;
+    for (axis, key) in CONTROLS_MIRROR_AXES.into_iter().enumerate() {
+        if !keyboard_input.just_pressed(key) {
+            continue;
+        }
+        let plane = if through_hovered {
+            let Some((x, y, z)) = vlox_settings.hovered else {
+                continue;
+            };
+            let center = vlox_settings.data.vlox_xyz_to_xyz_f32(x, y, z, depth);
+            Some([center.0, center.1, center.2][axis])
+        } else if vlox_settings.mirror.planes[axis].is_some() {
+            None
+        } else {
+            Some(0.0)
+        };
+        vlox_settings.mirror.planes[axis] = plane;
+    }
+}
+
+fn draw_mirror_planes(mut gizmos: Gizmos, vlox_settings: Res<VloxSettings>) {
+    let size = vlox_settings.data.size();
+    for (axis, plane) in vlox_settings.mirror.planes.iter().enumerate() {
+        let Some(plane) = *plane else {
+            continue;
+        };
+        let (position, rotation) = match axis {
+            0 => (Vec3::X, Quat::from_rotation_y(std::f32::consts::FRAC_PI_2)),
+            1 => (Vec3::Y, Quat::from_rotation_x(std::f32::consts::FRAC_PI_2)),
+            _ => (Vec3::Z, Quat::IDENTITY),
+        };
+        gizmos.rect(
+            Isometry3d::new(position * plane, rotation),
+            Vec2::splat(size),
+            MIRROR_PLANE_COLORS[axis],
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::vlox::VOID;
+    use super::*;
+
+    #[test]
+    fn mirror_index_through_the_centre_and_through_a_vlox() {
+        let data = VloxData::new(2);
+        // four vloxes a side at depth 2
+        assert_eq!(Some(3), mirror_index(&data, 0, 2, 0.0));
+        assert_eq!(Some(2), mirror_index(&data, 1, 2, 0.0));
+        assert_eq!(Some(0), mirror_index(&data, 3, 2, 0.0));
+
+        // a plane through the middle of vlox 1 keeps it in place, and pushes vlox 3 outside
+        let plane = data.vlox_xyz_to_xyz_f32(1, 0, 0, 2).0;
+        assert_eq!(Some(1), mirror_index(&data, 1, 2, plane));
+        assert_eq!(Some(0), mirror_index(&data, 2, 2, plane));
+        assert_eq!(None, mirror_index(&data, 3, 2, plane));
+    }
+
+    #[test]
+    fn images_across_each_axis_and_combined() {
+        let data = VloxData::new(2);
+        for axis in 0..3 {
+            let mut mirror = Mirror::default();
+            mirror.planes[axis] = Some(0.0);
+            let mut image = [0, 1, 2];
+            image[axis] = 3 - image[axis];
+            assert_eq!(
+                vec![(0, 1, 2), (image[0], image[1], image[2])],
+                mirror.cells(&data, (0, 1, 2), 2),
+                "axis {axis}"
+            );
+        }
+
+        let mirror = Mirror {
+            planes: [Some(0.0), None, Some(0.0)],
+        };
+        assert_eq!(
+            vec![(0, 1, 2), (3, 1, 2), (0, 1, 1), (3, 1, 1)],
+            mirror.cells(&data, (0, 1, 2), 2)
+        );
+        let mirror = Mirror {
+            planes: [Some(0.0); 3],
+        };
+        assert_eq!(8, mirror.cells(&data, (0, 1, 2), 2).len());
+    }
+
+    #[test]
+    fn images_on_the_plane_and_outside_the_data() {
+        let data = VloxData::new(2);
+        let plane = data.vlox_xyz_to_xyz_f32(1, 0, 0, 2).0;
+        let mirror = Mirror {
+            planes: [Some(plane), None, None],
+        };
+        // a vlox on the plane is its own image, and is only edited once
+        assert_eq!(
+            vec![Some((1, 0, 0)), Some((1, 0, 0))],
+            mirror.images(&data, (1, 0, 0), 2)
+        );
+        let edits = mirror.edits(&data, vec![VloxEdit::set((1, 0, 0), 2, 1)]);
+        assert_eq!(vec![VloxEdit::set((1, 0, 0), 2, 1)], edits);
+        // an edit whose image is edited as well is only mirrored once, while the tool's own
+        // edits to the same vlox are kept in order
+        let edits = vec![
+            VloxEdit::set((0, 0, 0), 2, VOID),
+            VloxEdit::set((2, 0, 0), 2, 1),
+            VloxEdit::set((0, 0, 0), 2, 1),
+        ];
+        assert_eq!(
+            vec![
+                VloxEdit::set((0, 0, 0), 2, VOID),
+                VloxEdit::set((2, 0, 0), 2, VOID),
+                VloxEdit::set((2, 0, 0), 2, 1),
+                VloxEdit::set((0, 0, 0), 2, 1),
+            ],
+            mirror.edits(&data, edits.clone())
+        );
+        assert_eq!(edits, Mirror::default().edits(&data, edits.clone()));
+
+        // images outside the data are left out, but keep their place in `images`
+        assert_eq!(
+            vec![Some((3, 0, 0)), None],
+            mirror.images(&data, (3, 0, 0), 2)
+        );
+        assert_eq!(vec![(3, 0, 0)], mirror.cells(&data, (3, 0, 0), 2));
+    }
+}
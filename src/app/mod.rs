@@ -1,21 +1,48 @@
 use bevy::{
     asset::RenderAssetUsages,
-    picking::pointer::{Location, PointerId, PointerInteraction, PointerLocation},
     prelude::*,
-    render::{
-        camera::NormalizedRenderTarget,
-        mesh::{
-            Indices, MeshAabb,
-            PrimitiveTopology::TriangleList,
-            VertexAttributeValues::{Float32x3, Float32x4},
-        },
+    render::mesh::{
+        Indices, MeshAabb,
+        PrimitiveTopology::TriangleList,
+        VertexAttributeValues::{Float32x3, Float32x4},
     },
-    window::{CursorGrabMode, WindowMode, WindowRef},
+    window::{CursorGrabMode, WindowMode},
 };
 use bevy_flycam::{FlyCam, NoCameraPlayerPlugin};
-use uuid::Uuid;
+use changes::ChangesPlugin;
+use clip::{ClipChanged, ClipPlugin};
+use dag::DagPlugin;
+use eyedropper::EyedropperPlugin;
+use gamepad::GamepadControlsPlugin;
+use hotbar::HotbarPlugin;
+use hud::HudPlugin;
+use input::{Action, InputPlugin};
+use lights::VloxLightsPlugin;
+use mirror::MirrorPlugin;
+use octree::OctreePlugin;
+use orbit::{OrbitCamera, OrbitPlugin};
+use palette::PalettePlugin;
+use tools::{EditOp, ToolButton, ToolContext, ToolRegistry, ToolsPlugin};
+use touch::{TouchControls, TouchControlsPlugin};
+use views::{AxisViews, AxisViewsPlugin};
 use vlox::VloxData;
 
+mod changes;
+mod clip;
+mod dag;
+mod eyedropper;
+mod gamepad;
+mod hotbar;
+mod hud;
+mod input;
+mod lights;
+mod mirror;
+mod octree;
+mod orbit;
+mod palette;
+mod tools;
+mod touch;
+mod views;
 mod vlox;
 
 const DEPTH_TO_UNIT: u8 = 2;
@@ -24,28 +51,42 @@ const MIN_VLOX_DEPTH: u8 = 1;
 const MAX_VLOX_DEPTH: u8 = 5;
 const INITIAL_VLOX_DEPTH: u8 = 5;
 const COMPUTE_MESH_DEPTH: u8 = 5;
-
-const CONTROLS_VLOX_SIZE_UP: KeyCode = KeyCode::Equal;
-const CONTROLS_VLOX_SIZE_DOWN: KeyCode = KeyCode::Minus;
+/// More single vlox changes than this in one frame are relit all at once
+const MAX_LIGHT_UPDATES: usize = 8;
 
 pub fn start() {
     let mut app = App::new();
 
-    app.add_plugins(MeshPickingPlugin)
-        .add_plugins(DefaultPlugins.set(WindowPlugin {
-            primary_window: Some(Window {
-                mode: WindowMode::BorderlessFullscreen(MonitorSelection::Current),
-                ..default()
-            }),
+    app.add_plugins(DefaultPlugins.set(WindowPlugin {
+        primary_window: Some(Window {
+            mode: WindowMode::BorderlessFullscreen(MonitorSelection::Current),
             ..default()
-        }))
-        .add_plugins(NoCameraPlayerPlugin)
-        .init_resource::<VloxSettings>()
-        .add_systems(Startup, setup)
-        .add_systems(Update, pause_resume)
-        .add_systems(Update, focus_camera)
-        .add_systems(Update, edit_mesh)
-        .add_systems(Update, update_pointer_location);
+        }),
+        ..default()
+    }))
+    .add_plugins(NoCameraPlayerPlugin)
+    .add_plugins(InputPlugin)
+    .add_plugins(GamepadControlsPlugin)
+    .add_plugins(TouchControlsPlugin)
+    .add_plugins(VloxLightsPlugin)
+    .add_plugins(PalettePlugin)
+    .add_plugins(HotbarPlugin)
+    .add_plugins(HudPlugin)
+    .add_plugins(ToolsPlugin)
+    .add_plugins(EyedropperPlugin)
+    .add_plugins(MirrorPlugin)
+    .add_plugins(ChangesPlugin)
+    .add_plugins(DagPlugin)
+    .add_plugins(OctreePlugin)
+    .add_plugins(ClipPlugin)
+    .add_plugins(OrbitPlugin)
+    .add_plugins(AxisViewsPlugin)
+    .init_resource::<VloxSettings>()
+    .add_event::<VloxChanged>()
+    .add_systems(Startup, setup)
+    .add_systems(Update, pause_resume)
+    .add_systems(Update, focus_camera)
+    .add_systems(Update, (edit_mesh, update_mesh).chain());
 
     #[cfg(target_arch = "wasm32")]
     {
@@ -68,23 +109,16 @@ pub fn start() {
     app.run();
 }
 
-fn update_pointer_location(
-    mut pointer: Single<&mut PointerLocation, With<Camera>>,
-    win: Single<(Entity, &Window)>,
-) {
-    if let Some(location) = &mut pointer.location {
-        let window = win.1;
-        let width = window.width();
-        let height = window.height();
-
-        location.position = Vec2::new(width / 2.0, height / 2.0)
-    }
-}
 fn focus_camera(
     mut camera: Single<&mut Transform, With<Camera>>,
-    keyboard_input: Res<ButtonInput<KeyCode>>,
+    actions: Res<ButtonInput<Action>>,
+    orbit: Res<OrbitCamera>,
 ) {
-    if keyboard_input.just_pressed(KeyCode::KeyF) {
+    // the orbit camera refocuses itself
+    if orbit.active {
+        return;
+    }
+    if actions.just_pressed(Action::Focus) {
         camera.look_at(Vec3::ZERO, Vec3::Y);
     }
 }
@@ -93,7 +127,12 @@ fn pause_resume(
     mut window: Single<&mut Window>,
     mouse: Res<ButtonInput<MouseButton>>,
     key: Res<ButtonInput<KeyCode>>,
+    touch: Res<TouchControls>,
 ) {
+    // touch devices look around by dragging, and phones don't like fullscreen
+    if touch.active {
+        return;
+    }
     if mouse.any_just_pressed(vec![MouseButton::Left, MouseButton::Right]) {
         window.cursor_options.visible = false;
         window.cursor_options.grab_mode = CursorGrabMode::Locked;
@@ -124,34 +163,13 @@ fn setup(
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
     mut vlox_settings: ResMut<VloxSettings>,
-    win: Single<(Entity, &Window)>,
+    mut vlox_changed: EventWriter<VloxChanged>,
 ) {
-    let window_entity = win.0;
-    let window = win.1;
-    let width = window.width();
-    let height = window.height();
-
-    // light
-    commands.spawn((
-        PointLight {
-            shadows_enabled: true,
-            ..default()
-        },
-        Transform::from_xyz(0.0, 0.0, 0.0),
-    ));
-
     // camera
     commands.spawn((
         Camera3d::default(),
         Transform::from_xyz(0.0, 0.0, -10.0).looking_at(Vec3::ZERO, Vec3::Y),
         FlyCam,
-        PointerId::Custom(Uuid::new_v4()),
-        PointerLocation::new(Location {
-            target: NormalizedRenderTarget::Window(
-                WindowRef::Primary.normalize(Some(window_entity)).unwrap(),
-            ),
-            position: Vec2::new(width / 2.0, height / 2.0),
-        }),
     ));
 
     // Custom mesh
@@ -170,6 +188,8 @@ fn setup(
     vlox_settings.data.set(0, 0, 2, 2, 1);
     vlox_settings.data.set(0, 0, 3, 2, 1);
 
+    vlox_settings.data.set(3, 3, 3, 2, 5);
+
     vlox_settings.selected_depth = INITIAL_VLOX_DEPTH;
     vlox_settings.selected_value = 1;
     vlox_settings.materials.set(0, vlox::Material::Void);
@@ -179,6 +199,9 @@ fn setup(
             name: "White".to_string(),
             data: VloxData::new(0),
             colors: vec![vlox::Color::new(1.0, 1.0, 1.0, 1.0)],
+            emissive: 0.0,
+            metallic: 0.0,
+            roughness: 0.5,
         }),
     );
     vlox_settings.materials.set(
@@ -187,6 +210,9 @@ fn setup(
             name: "Red".to_string(),
             data: VloxData::new(0),
             colors: vec![vlox::Color::new(1.0, 0.0, 0.0, 1.0)],
+            emissive: 0.0,
+            metallic: 0.0,
+            roughness: 0.5,
         }),
     );
     vlox_settings.materials.set(
@@ -195,6 +221,9 @@ fn setup(
             name: "Green".to_string(),
             data: VloxData::new(0),
             colors: vec![vlox::Color::new(0.0, 1.0, 0.0, 1.0)],
+            emissive: 0.0,
+            metallic: 0.0,
+            roughness: 0.5,
         }),
     );
     vlox_settings.materials.set(
@@ -203,180 +232,195 @@ fn setup(
             name: "Blue".to_string(),
             data: VloxData::new(0),
             colors: vec![vlox::Color::new(0.0, 0.0, 1.0, 1.0)],
+            emissive: 0.0,
+            metallic: 0.0,
+            roughness: 0.5,
+        }),
+    );
+    vlox_settings.materials.set(
+        5,
+        vlox::Material::Solid(vlox::SolidMaterial {
+            name: "Lamp".to_string(),
+            data: VloxData::new(0),
+            colors: vec![vlox::Color::new(1.0, 0.9, 0.6, 1.0)],
+            emissive: 10.0,
+            metallic: 0.0,
+            roughness: 0.5,
         }),
     );
 
-    let (vertices, normals, colors, indices) = vlox_settings
-        .data
-        .compute_mesh_at_depth(COMPUTE_MESH_DEPTH, &vlox_settings.materials);
-    let mut mesh = Mesh::new(TriangleList, RenderAssetUsages::default());
-    set_vlox_mesh(&mut mesh, vertices, normals, colors, indices);
+    // the mesh is filled in by update_mesh
+    let mesh = Mesh::new(TriangleList, RenderAssetUsages::default());
     commands.spawn((
         Mesh3d(meshes.add(mesh)),
         MeshMaterial3d(materials.add(Color::srgb(0.8, 0.7, 0.6))),
         Transform::from_xyz(0.0, 0.0, 0.0),
         MainMesh,
     ));
+    vlox_changed.send(VloxChanged::All);
 }
 
-/// A system that draws hit indicators for every pointer.
+/// Uses the active tool where the camera is looking, and draws its preview.
 fn edit_mesh(
-    pointers: Query<(&PointerInteraction, &PointerId)>,
+    (camera, touch): (Single<(&Transform, &Camera)>, Res<TouchControls>),
     mut gizmos: Gizmos,
-    mouse_button_input: Res<ButtonInput<MouseButton>>,
+    (actions, keyboard_input): (Res<ButtonInput<Action>>, Res<ButtonInput<KeyCode>>),
     mut vlox_settings: ResMut<VloxSettings>,
-    main_mesh: Single<(&Mesh3d, &MainMesh)>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut tools: ResMut<ToolRegistry>,
+    views: Res<AxisViews>,
+    mut vlox_changed: EventWriter<VloxChanged>,
 ) {
-    for (point, normal) in pointers
-        .iter()
-        .filter_map(|(interaction, id)| {
-            if !id.is_custom() {
-                return None;
-            } else {
-                interaction.get_nearest_hit()
-            }
-        })
-        .filter_map(|(_entity, hit)| hit.position.zip(hit.normal))
-    {
-        let depth = vlox_settings.selected_depth;
-        let vlox_size = vlox_settings
-            .data
-            .vlox_size(vlox_settings.data.num_vlox(depth));
-        let half_vlox = vlox_size / 2.0;
-
-        let preview_xyz = point + normal * half_vlox;
-        let (vx, vy, vz) = vlox_settings.data.xyz_f32_to_vlox_xyz(
-            preview_xyz.x,
-            preview_xyz.y,
-            preview_xyz.z,
-            depth,
-        );
-        let (preview_x, preview_y, preview_z) =
-            vlox_settings.data.vlox_xyz_to_xyz_f32(vx, vy, vz, depth);
-        let preview_xyz = Vec3::new(preview_x, preview_y, preview_z);
-        gizmos.cuboid(
-            Transform::from_translation(preview_xyz).with_scale(Vec3::ONE * vlox_size),
-            Color::WHITE,
-        );
-
-        let (vx, vy, vz) = vlox_settings
-            .data
-            .xyz_f32_to_vlox_xyz(point.x, point.y, point.z, depth);
-        println!("{},{},{} depth: {}", vx, vy, vz, depth);
-
-        if mouse_button_input.just_pressed(MouseButton::Left) {
-            let point = point + normal * half_vlox;
-
-            let bounds = vlox_settings.data.size() / 2.0;
-            if !(point.x >= bounds
-                || point.x <= -bounds
-                || point.y >= bounds
-                || point.y <= -bounds
-                || point.z >= bounds
-                || point.z <= -bounds)
-            {
-                if let Some(mesh) = meshes.get_mut(&main_mesh.0 .0) {
-                    let (vx, vy, vz) = vlox_settings
-                        .data
-                        .xyz_f32_to_vlox_xyz(point.x, point.y, point.z, depth);
-                    let selected_value = vlox_settings.selected_value;
-                    vlox_settings.data.set(vx, vy, vz, depth, selected_value);
-
-                    let (vertices, normals, colors, indices) = vlox_settings
-                        .data
-                        .compute_mesh_at_depth(COMPUTE_MESH_DEPTH, &vlox_settings.materials);
-                    set_vlox_mesh(mesh, vertices, normals, colors, indices);
-                }
-            }
-        } else if mouse_button_input.just_pressed(MouseButton::Right) {
-            let depth = vlox_settings.selected_depth;
-            let half_vlox = vlox_settings
-                .data
-                .vlox_size(vlox_settings.data.num_vlox(depth))
-                / 2.0;
-            let point = point - normal * half_vlox;
-
-            let bounds = vlox_settings.data.size() / 2.0;
-            if !(point.x >= bounds
-                || point.x <= -bounds
-                || point.y >= bounds
-                || point.y <= -bounds
-                || point.z >= bounds
-                || point.z <= -bounds)
-            {
-                if let Some(mesh) = meshes.get_mut(&main_mesh.0 .0) {
-                    let (vx, vy, vz) = vlox_settings
-                        .data
-                        .xyz_f32_to_vlox_xyz(point.x, point.y, point.z, depth);
-                    vlox_settings.data.set(vx, vy, vz, depth, 0);
+    let depth = vlox_settings.selected_depth;
+    let (transform, camera) = *camera;
+    let ray = touch::aim(&touch, camera, transform);
+    let (origin, direction) = (ray.origin, *ray.direction);
+    let hit = vlox_settings.data.raycast(
+        origin.into(),
+        direction.into(),
+        COMPUTE_MESH_DEPTH,
+        vlox_settings.clip.as_ref(),
+    );
+    let on_plane = views.place_on_plane(&vlox_settings.data, origin, direction, depth);
+    let (target, place) = match (hit, on_plane) {
+        // in an axis view, vloxes are placed on the working plane unless something is in front
+        (hit, Some((place, distance))) if hit.is_none_or(|hit| hit.distance > distance) => {
+            (None, place)
+        }
+        (Some(hit), _) => {
+            let point = origin + direction * hit.distance;
+            let (target, place) = hit.cells(&vlox_settings.data, point.into(), depth);
+            (Some(target), place)
+        }
+        (None, _) => (None, None),
+    };
+    vlox_settings.hovered = target;
+
+    let button = if eyedropper::picking(&keyboard_input) {
+        None
+    } else if actions.just_pressed(Action::Place) {
+        Some(ToolButton::Primary)
+    } else if actions.just_pressed(Action::Erase) {
+        Some(ToolButton::Secondary)
+    } else {
+        None
+    };
+    let ctx = ToolContext {
+        data: &vlox_settings.data,
+        depth,
+        material: vlox_settings.selected_value,
+        target,
+        hit_value: target.and(hit).map(|hit| hit.value),
+        place,
+    };
+    let mirror = &vlox_settings.mirror;
+    let mut edits = vec![];
+    if let Some(tool) = tools.active_mut() {
+        if let Some(button) = button {
+            edits = mirror.edits(ctx.data, tool.apply(&ctx, button));
+        }
+        edits.extend(mirror.edits(ctx.data, tool.keys(&ctx, &keyboard_input)));
+        let previews = mirror.previews(ctx.data, depth, tool.preview(&ctx));
+        tools::draw_previews(&mut gizmos, ctx.data, depth, &previews, tool.color());
+    }
 
-                    let (vertices, normals, colors, indices) = vlox_settings
-                        .data
-                        .compute_mesh_at_depth(COMPUTE_MESH_DEPTH, &vlox_settings.materials);
-                    set_vlox_mesh(mesh, vertices, normals, colors, indices);
-                    info!("updated mesh")
-                }
-            }
+    for edit in edits {
+        if edit.apply(&mut vlox_settings.data) {
+            let (x, y, z) = edit.cell;
+            vlox_changed.send(match edit.op {
+                EditOp::Fill(_) => VloxChanged::All,
+                op if op.is_global() => VloxChanged::All,
+                _ => VloxChanged::Vlox(x, y, z, edit.depth),
+            });
         }
     }
-    if keyboard_input.just_pressed(CONTROLS_VLOX_SIZE_UP)
-        && vlox_settings.selected_depth > MIN_VLOX_DEPTH
-    {
+
+    if actions.just_pressed(Action::VloxSizeUp) && vlox_settings.selected_depth > MIN_VLOX_DEPTH {
         vlox_settings.selected_depth -= 1;
-        println!("new depth: {}", vlox_settings.selected_depth);
     }
-    if keyboard_input.just_pressed(CONTROLS_VLOX_SIZE_DOWN)
-        && vlox_settings.selected_depth < MAX_VLOX_DEPTH
-    {
+    if actions.just_pressed(Action::VloxSizeDown) && vlox_settings.selected_depth < MAX_VLOX_DEPTH {
         vlox_settings.selected_depth += 1;
-        println!("new depth: {}", vlox_settings.selected_depth);
-    }
-
-    if keyboard_input.just_pressed(KeyCode::Digit1) {
-        vlox_settings.selected_value = 1;
-    }
-    if keyboard_input.just_pressed(KeyCode::Digit2) {
-        vlox_settings.selected_value = 2;
-    }
-    if keyboard_input.just_pressed(KeyCode::Digit3) {
-        vlox_settings.selected_value = 3;
-    }
-    if keyboard_input.just_pressed(KeyCode::Digit4) {
-        vlox_settings.selected_value = 4;
-    }
-    if keyboard_input.just_pressed(KeyCode::Digit5) {
-        //vlox_settings.selected_value = 5;
-    }
-    if keyboard_input.just_pressed(KeyCode::Digit6) {
-        //vlox_settings.selected_value = 6;
-    }
-    if keyboard_input.just_pressed(KeyCode::Digit7) {
-        //vlox_settings.selected_value = 7;
-    }
-    if keyboard_input.just_pressed(KeyCode::Digit8) {
-        //vlox_settings.selected_value = 8;
-    }
-    if keyboard_input.just_pressed(KeyCode::Digit9) {
-        //vlox_settings.selected_value = 9;
-    }
-    if keyboard_input.just_pressed(KeyCode::Digit0) {
-        //vlox_settings.selected_value = 0;
     }
 }
 
+/// The vlox at `depth` containing a point, if the point is inside the data
 #[derive(Resource, Default)]
 struct VloxSettings {
     selected_value: vlox::MaterialId,
     selected_depth: u8,
     data: vlox::VloxData,
     materials: vlox::MaterialMap,
+    light: vlox::LightMap,
+    /// The vlox under the crosshair at the selected depth
+    hovered: Option<(u128, u128, u128)>,
+    mirror: mirror::Mirror,
+    /// Everything beyond this plane is left out of the mesh and ignored by raycasts
+    clip: Option<vlox::ClipPlane>,
 }
 
 #[derive(Component)]
 struct MainMesh;
 
+/// Sent whenever `VloxSettings.data` or the materials are edited. Moving the clipping plane
+/// sends `ClipChanged` instead.
+#[derive(Event)]
+enum VloxChanged {
+    /// A single vlox changed: x, y, z and depth
+    Vlox(u128, u128, u128, u8),
+    /// Anything may have changed, including the materials
+    All,
+}
+
+/// Relights and remeshes the main mesh after the vlox data changes, and only remeshes it
+/// after the clipping plane moves
+fn update_mesh(
+    (mut changes, mut clip_changed): (EventReader<VloxChanged>, EventReader<ClipChanged>),
+    mut vlox_settings: ResMut<VloxSettings>,
+    main_mesh: Single<&Mesh3d, With<MainMesh>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    if changes.is_empty() && clip_changed.is_empty() {
+        return;
+    }
+    clip_changed.clear();
+
+    let vlox_settings = &mut *vlox_settings;
+    let changes: Vec<&VloxChanged> = changes.read().collect();
+    let relight_all = changes.len() > MAX_LIGHT_UPDATES
+        || changes
+            .iter()
+            .any(|change| matches!(change, VloxChanged::All));
+    if relight_all {
+        vlox_settings.light = vlox::LightMap::new(
+            &vlox_settings.data,
+            &vlox_settings.materials,
+            COMPUTE_MESH_DEPTH,
+        );
+    } else {
+        for change in changes {
+            if let VloxChanged::Vlox(x, y, z, depth) = *change {
+                vlox_settings.light.update(
+                    &vlox_settings.data,
+                    &vlox_settings.materials,
+                    x,
+                    y,
+                    z,
+                    depth,
+                );
+            }
+        }
+    }
+
+    if let Some(mesh) = meshes.get_mut(&main_mesh.0) {
+        let (vertices, normals, colors, indices) = vlox_settings.data.compute_mesh_at_depth(
+            COMPUTE_MESH_DEPTH,
+            &vlox_settings.materials,
+            Some(&vlox_settings.light),
+            vlox_settings.clip.as_ref(),
+        );
+        set_vlox_mesh(mesh, vertices, normals, colors, indices);
+    }
+}
+
 fn set_vlox_mesh(
     mesh: &mut Mesh,
     vertices: Vec<[f32; 3]>,
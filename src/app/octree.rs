@@ -0,0 +1,191 @@
+use bevy::prelude::*;
+
+use super::{tools::ToolRegistry, VloxChanged, VloxSettings};
+
+/// Toggles the subdivision overlay for every tool, it is always on for the subdivide tool
+const CONTROLS_TOGGLE_SUBDIVISIONS: KeyCode = KeyCode::F6;
+/// How far below the targeted vlox the overlay goes
+const SUBDIVISION_LEVELS: u8 = 3;
+
+const SUBDIVISION_COLOR: Color = Color::srgb(0.6, 1.0, 1.0);
+
+/// Toggles the debug view of every stored node
+const CONTROLS_TOGGLE_STRUCTURE: KeyCode = KeyCode::F7;
+/// Lower and raise the deepest depth drawn, with Shift the shallowest instead
+const CONTROLS_STRUCTURE_DEPTH_DOWN: KeyCode = KeyCode::Comma;
+const CONTROLS_STRUCTURE_DEPTH_UP: KeyCode = KeyCode::Period;
+const CONTROLS_STRUCTURE_MIN_DEPTH_MODIFIERS: [KeyCode; 2] =
+    [KeyCode::ShiftLeft, KeyCode::ShiftRight];
+/// Halve and double how far from the camera nodes are drawn
+const CONTROLS_STRUCTURE_NEARER: KeyCode = KeyCode::Semicolon;
+const CONTROLS_STRUCTURE_FURTHER: KeyCode = KeyCode::Quote;
+
+const STRUCTURE_DISTANCE: f32 = 16.0;
+const MIN_STRUCTURE_DISTANCE: f32 = 0.25;
+/// Hue step between the colours of consecutive depths
+const STRUCTURE_HUE_STEP: f32 = 47.0;
+
+/// Wireframes of how the targeted vlox is subdivided, and a debug view of the whole octree
+pub struct OctreePlugin;
+impl Plugin for OctreePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<OctreeOverlay>().add_systems(
+            Update,
+            (
+                (toggle_overlay, update_counts).chain(),
+                draw_subdivisions,
+                draw_structure,
+            ),
+        );
+    }
+}
+
+#[derive(Resource)]
+pub struct OctreeOverlay {
+    subdivisions: bool,
+    structure: bool,
+    /// Shallowest and deepest depth the structure view draws
+    depths: (u8, u8),
+    /// Furthest distance from the camera the structure view draws nodes at
+    max_distance: f32,
+    /// Stored nodes and leaves at each depth, only counted while the structure view is on
+    pub counts: Vec<(usize, usize)>,
+}
+impl Default for OctreeOverlay {
+    fn default() -> Self {
+        Self {
+            subdivisions: false,
+            structure: false,
+            depths: (0, u8::MAX),
+            max_distance: STRUCTURE_DISTANCE,
+            counts: vec![],
+        }
+    }
+}
+
+fn toggle_overlay(keyboard_input: Res<ButtonInput<KeyCode>>, mut overlay: ResMut<OctreeOverlay>) {
+    if keyboard_input.just_pressed(CONTROLS_TOGGLE_SUBDIVISIONS) {
+        overlay.subdivisions = !overlay.subdivisions;
+    }
+    if keyboard_input.just_pressed(CONTROLS_TOGGLE_STRUCTURE) {
+        overlay.structure = !overlay.structure;
+    }
+    if !overlay.structure {
+        return;
+    }
+
+    let (min, max) = &mut overlay.depths;
+    let depth = if keyboard_input.any_pressed(CONTROLS_STRUCTURE_MIN_DEPTH_MODIFIERS) {
+        min
+    } else {
+        max
+    };
+    if keyboard_input.just_pressed(CONTROLS_STRUCTURE_DEPTH_DOWN) {
+        *depth = depth.saturating_sub(1);
+    }
+    if keyboard_input.just_pressed(CONTROLS_STRUCTURE_DEPTH_UP) {
+        *depth = depth.saturating_add(1);
+    }
+    if keyboard_input.just_pressed(CONTROLS_STRUCTURE_NEARER) {
+        overlay.max_distance = (overlay.max_distance * 0.5).max(MIN_STRUCTURE_DISTANCE);
+    }
+    if keyboard_input.just_pressed(CONTROLS_STRUCTURE_FURTHER) {
+        overlay.max_distance *= 2.0;
+    }
+}
+
+/// Counting walks the whole tree, so it is only redone when the data changes
+fn update_counts(
+    mut changes: EventReader<VloxChanged>,
+    vlox_settings: Res<VloxSettings>,
+    mut overlay: ResMut<OctreeOverlay>,
+) {
+    let changed = changes.read().count() > 0;
+    if !overlay.structure {
+        overlay.counts.clear();
+        return;
+    }
+    if !changed && !overlay.counts.is_empty() {
+        return;
+    }
+
+    let mut counts = vec![];
+    vlox_settings.data.walk_stored_nodes(u8::MAX, &mut |node| {
+        let depth = node.depth as usize;
+        if counts.len() <= depth {
+            counts.resize(depth + 1, (0, 0));
+        }
+        counts[depth].0 += 1;
+        counts[depth].1 += node.leaf as usize;
+        true
+    });
+    // keep the depth range within the stored depths
+    let deepest = counts.len() as u8 - 1;
+    overlay.depths.1 = overlay.depths.1.min(deepest);
+    overlay.depths.0 = overlay.depths.0.min(overlay.depths.1);
+    overlay.counts = counts;
+}
+
+fn draw_subdivisions(
+    mut gizmos: Gizmos,
+    vlox_settings: Res<VloxSettings>,
+    tools: Res<ToolRegistry>,
+    overlay: Res<OctreeOverlay>,
+) {
+    let subdividing = tools
+        .active()
+        .is_some_and(|tool| tool.name() == "Subdivide");
+    let Some(target) = vlox_settings.hovered else {
+        return;
+    };
+    if !overlay.subdivisions && !subdividing {
+        return;
+    }
+
+    let data = &vlox_settings.data;
+    let depth = vlox_settings.selected_depth;
+    for node in data.stored_nodes(target, depth, depth + SUBDIVISION_LEVELS) {
+        // the targeted vlox itself is outlined by the tool preview
+        if node.depth == depth {
+            continue;
+        }
+        let (x, y, z) = data.vlox_xyz_to_xyz_f32(node.x, node.y, node.z, node.depth);
+        let size = data.vlox_size(data.num_vlox(node.depth));
+        // deeper levels fade out
+        let alpha = 0.8 / (node.depth - depth) as f32;
+        gizmos.cuboid(
+            Transform::from_xyz(x, y, z).with_scale(Vec3::splat(size)),
+            SUBDIVISION_COLOR.with_alpha(alpha),
+        );
+    }
+}
+
+fn draw_structure(
+    mut gizmos: Gizmos,
+    camera: Single<&Transform, With<Camera>>,
+    vlox_settings: Res<VloxSettings>,
+    overlay: Res<OctreeOverlay>,
+) {
+    if !overlay.structure {
+        return;
+    }
+
+    let data = &vlox_settings.data;
+    let (min, max) = overlay.depths;
+    data.walk_stored_nodes(max, &mut |node| {
+        let center = Vec3::from(data.vlox_xyz_to_xyz_f32(node.x, node.y, node.z, node.depth));
+        let size = data.vlox_size(data.num_vlox(node.depth));
+        // nothing inside a node is nearer than its nearest point, so far nodes are skipped whole
+        let offset = (camera.translation - center).abs() - Vec3::splat(size * 0.5);
+        if offset.max(Vec3::ZERO).length() > overlay.max_distance {
+            return false;
+        }
+        if node.depth >= min && center.distance(camera.translation) <= overlay.max_distance {
+            gizmos.cuboid(
+                Transform::from_translation(center).with_scale(Vec3::splat(size)),
+                Color::hsl(node.depth as f32 * STRUCTURE_HUE_STEP % 360.0, 1.0, 0.5),
+            );
+        }
+        true
+    });
+}
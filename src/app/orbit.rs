@@ -0,0 +1,236 @@
+use bevy::{
+    input::mouse::{AccumulatedMouseMotion, AccumulatedMouseScroll, MouseScrollUnit},
+    prelude::*,
+    window::CursorGrabMode,
+};
+use bevy_flycam::FlyCam;
+
+use super::{input::Action, VloxSettings};
+
+/// Switches between the fly camera and orbiting around a focus point
+const CONTROLS_TOGGLE_ORBIT: KeyCode = KeyCode::KeyI;
+/// Held while moving the mouse to pan instead of orbiting
+const CONTROLS_ORBIT_PAN_MODIFIERS: [KeyCode; 2] = [KeyCode::ShiftLeft, KeyCode::ShiftRight];
+
+/// Radians turned per pixel of mouse movement
+const ORBIT_SENSITIVITY: f32 = 0.004;
+/// Fraction of the distance to the focus panned per pixel of mouse movement
+const PAN_SENSITIVITY: f32 = 0.002;
+/// Distance multiplier for each line scrolled
+const ZOOM_STEP: f32 = 0.9;
+/// Pixels of touchpad scrolling that count as one line
+const PIXELS_PER_LINE: f32 = 100.0;
+const MIN_DISTANCE: f32 = 0.25;
+const MAX_DISTANCE: f32 = 100.0;
+/// Stops the camera looking straight up or down, where yaw is undefined
+const MAX_PITCH: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
+/// How quickly the camera catches up with where it should be, higher is faster
+const SMOOTHING: f32 = 12.0;
+
+/// A turntable camera that orbits around a focus point, with zoom towards the cursor and
+/// panning. The fly camera is disabled while it is active.
+pub struct OrbitPlugin;
+impl Plugin for OrbitPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<OrbitCamera>()
+            .add_systems(Update, (toggle_orbit, control_orbit, move_camera).chain());
+    }
+}
+
+#[derive(Resource)]
+pub struct OrbitCamera {
+    pub active: bool,
+    focus: Vec3,
+    /// Angle around the vertical axis through the focus, zero looking along -z
+    yaw: f32,
+    /// Angle above the horizontal plane through the focus
+    pitch: f32,
+    distance: f32,
+}
+impl Default for OrbitCamera {
+    fn default() -> Self {
+        Self {
+            active: false,
+            focus: Vec3::ZERO,
+            yaw: 0.0,
+            pitch: 0.0,
+            distance: 10.0,
+        }
+    }
+}
+impl OrbitCamera {
+    /// Where the camera should be
+    fn transform(&self) -> Transform {
+        let offset = Vec3::new(
+            self.pitch.cos() * self.yaw.sin(),
+            self.pitch.sin(),
+            self.pitch.cos() * self.yaw.cos(),
+        );
+        Transform::from_translation(self.focus + offset * self.distance)
+            .looking_at(self.focus, Vec3::Y)
+    }
+    /// Orbits around `focus` from wherever the camera is now
+    fn look_from(&mut self, translation: Vec3, focus: Vec3) {
+        let offset = translation - focus;
+        self.focus = focus;
+        self.distance = offset.length().clamp(MIN_DISTANCE, MAX_DISTANCE);
+        let offset = offset.normalize_or(Vec3::Z);
+        self.yaw = offset.x.atan2(offset.z);
+        self.pitch = offset.y.asin().clamp(-MAX_PITCH, MAX_PITCH);
+    }
+    /// Moves the camera `scale` times as far from `point`, keeping its direction, so `point`
+    /// stays in the same place on screen
+    fn zoom_towards(&mut self, point: Vec3, scale: f32) {
+        self.focus = point + (self.focus - point) * scale;
+        self.distance *= scale;
+    }
+}
+
+/// The centre of the hovered vlox, or of the whole object
+fn focus_point(vlox_settings: &VloxSettings) -> Vec3 {
+    match vlox_settings.hovered {
+        Some((x, y, z)) => vlox_settings
+            .data
+            .vlox_xyz_to_xyz_f32(x, y, z, vlox_settings.selected_depth)
+            .into(),
+        None => Vec3::ZERO,
+    }
+}
+
+fn toggle_orbit(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    actions: Res<ButtonInput<Action>>,
+    camera: Single<(Entity, &Transform), With<Camera>>,
+    vlox_settings: Res<VloxSettings>,
+    mut orbit: ResMut<OrbitCamera>,
+) {
+    let (entity, transform) = *camera;
+    if keyboard_input.just_pressed(CONTROLS_TOGGLE_ORBIT) {
+        orbit.active = !orbit.active;
+        if orbit.active {
+            commands.entity(entity).remove::<FlyCam>();
+            orbit.look_from(transform.translation, focus_point(&vlox_settings));
+        } else {
+            // the fly camera carries on from wherever the orbit left it
+            commands.entity(entity).insert(FlyCam);
+        }
+    }
+    // focusing moves the orbit to the hovered vlox, or the centre of the object
+    if orbit.active && actions.just_pressed(Action::Focus) {
+        orbit.look_from(transform.translation, focus_point(&vlox_settings));
+    }
+}
+
+fn control_orbit(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    (mouse_motion, mouse_scroll): (Res<AccumulatedMouseMotion>, Res<AccumulatedMouseScroll>),
+    window: Single<&Window>,
+    camera: Single<(&Camera, &GlobalTransform)>,
+    mut orbit: ResMut<OrbitCamera>,
+) {
+    if !orbit.active {
+        return;
+    }
+
+    // like the fly camera, looking around needs the cursor to be grabbed
+    let grabbed = window.cursor_options.grab_mode != CursorGrabMode::None;
+    let motion = mouse_motion.delta;
+    if grabbed && motion != Vec2::ZERO {
+        if keyboard_input.any_pressed(CONTROLS_ORBIT_PAN_MODIFIERS) {
+            let rotation = orbit.transform().rotation;
+            let pan = rotation * Vec3::new(-motion.x, motion.y, 0.0) * PAN_SENSITIVITY;
+            let distance = orbit.distance;
+            orbit.focus += pan * distance;
+        } else {
+            let turn = motion * ORBIT_SENSITIVITY;
+            orbit.yaw -= turn.x;
+            orbit.pitch = (orbit.pitch + turn.y).clamp(-MAX_PITCH, MAX_PITCH);
+        }
+    }
+
+    let lines = match mouse_scroll.unit {
+        MouseScrollUnit::Line => mouse_scroll.delta.y,
+        MouseScrollUnit::Pixel => mouse_scroll.delta.y / PIXELS_PER_LINE,
+    };
+    if lines == 0.0 {
+        return;
+    }
+    let distance = (orbit.distance * ZOOM_STEP.powf(lines)).clamp(MIN_DISTANCE, MAX_DISTANCE);
+    let scale = distance / orbit.distance;
+
+    // zoom towards where the cursor points on the plane through the focus, so that point
+    // stays under the cursor. A grabbed cursor is always in the centre.
+    let (camera, camera_transform) = *camera;
+    let cursor = match window.cursor_position() {
+        Some(cursor) if !grabbed => cursor,
+        _ => window.size() * 0.5,
+    };
+    let plane = InfinitePlane3d::new(camera_transform.forward());
+    let point = camera
+        .viewport_to_world(camera_transform, cursor)
+        .ok()
+        .and_then(|ray| Some(ray.get_point(ray.intersect_plane(orbit.focus, plane)?)));
+    match point {
+        Some(point) => orbit.zoom_towards(point, scale),
+        None => orbit.distance = distance,
+    }
+}
+
+/// Eases the camera towards the orbit, so switching modes and refocusing don't jump
+fn move_camera(
+    time: Res<Time>,
+    mut camera: Single<&mut Transform, With<Camera>>,
+    orbit: Res<OrbitCamera>,
+) {
+    if !orbit.active {
+        return;
+    }
+    let target = orbit.transform();
+    let t = 1.0 - (-SMOOTHING * time.delta_secs()).exp();
+    camera.translation = camera.translation.lerp(target.translation, t);
+    camera.rotation = camera.rotation.slerp(target.rotation, t);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn look_from_keeps_the_camera_where_it_is() {
+        let mut orbit = OrbitCamera::default();
+        let focus = Vec3::new(1.0, -2.0, 0.5);
+        for translation in [
+            Vec3::new(0.0, 0.0, -10.0),
+            Vec3::new(3.0, 4.0, 5.0),
+            Vec3::new(-6.0, -1.0, 2.0),
+        ] {
+            orbit.look_from(translation, focus);
+            let transform = orbit.transform();
+            assert!(transform.translation.distance(translation) < 1e-4);
+            assert!(transform.forward().dot((focus - translation).normalize()) > 0.9999);
+        }
+    }
+
+    #[test]
+    fn zoom_keeps_the_point_under_the_cursor() {
+        let mut orbit = OrbitCamera::default();
+        orbit.look_from(Vec3::new(3.0, 4.0, 5.0), Vec3::ZERO);
+        let before = orbit.transform();
+        // a point off to the side of the focus, on the plane facing the camera
+        let point = before.translation + before.forward() * orbit.distance + before.right() * 2.0;
+
+        orbit.zoom_towards(point, 0.5);
+        let after = orbit.transform();
+        assert!((orbit.distance - before.translation.length() * 0.5).abs() < 1e-4);
+        assert!(after.rotation.angle_between(before.rotation) < 1e-3);
+        let direction =
+            |camera: Transform| camera.rotation.inverse() * (point - camera.translation);
+        assert!(
+            direction(after)
+                .normalize()
+                .distance(direction(before).normalize())
+                < 1e-4
+        );
+    }
+}
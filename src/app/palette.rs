@@ -0,0 +1,78 @@
+use bevy::prelude::*;
+
+use super::{changes, vlox, VloxChanged, VloxSettings};
+
+/// Where `CONTROLS_SAVE_PALETTE` writes the current palette
+const PALETTE_SAVE_PATH: &str = "palette.ron";
+const CONTROLS_SAVE_PALETTE: KeyCode = KeyCode::KeyP;
+
+/// Swaps the material palette for any palette file dropped onto the window,
+/// and saves the current one with Ctrl+P.
+///
+/// Neither works in the web build yet, which has no file system and gets no file drops.
+pub struct PalettePlugin;
+impl Plugin for PalettePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (load_dropped_palette, save_palette));
+    }
+}
+
+fn load_dropped_palette(
+    mut drops: EventReader<FileDragAndDrop>,
+    mut vlox_settings: ResMut<VloxSettings>,
+    mut vlox_changed: EventWriter<VloxChanged>,
+) {
+    for drop in drops.read() {
+        let FileDragAndDrop::DroppedFile { path_buf, .. } = drop else {
+            continue;
+        };
+        let Some(format) = path_buf.extension().and_then(|extension| {
+            vlox::PaletteFormat::from_extension(&extension.to_string_lossy())
+        }) else {
+            // patches are applied by the changes plugin
+            if path_buf
+                .extension()
+                .and_then(|extension| extension.to_str())
+                != Some(changes::PATCH_EXTENSION)
+            {
+                warn!("not a palette file: {}", path_buf.display());
+            }
+            continue;
+        };
+
+        let materials = std::fs::read(path_buf)
+            .map_err(|error| error.to_string())
+            .and_then(|bytes| {
+                vlox::MaterialMap::from_palette(&bytes, format).map_err(|error| error.to_string())
+            });
+        match materials {
+            Ok(materials) => {
+                vlox_settings.materials = materials;
+                vlox_changed.send(VloxChanged::All);
+                info!("loaded palette {}", path_buf.display());
+            }
+            Err(error) => error!("couldn't load palette {}: {error}", path_buf.display()),
+        }
+    }
+}
+
+fn save_palette(keyboard_input: Res<ButtonInput<KeyCode>>, vlox_settings: Res<VloxSettings>) {
+    if !(keyboard_input.pressed(KeyCode::ControlLeft)
+        || keyboard_input.pressed(KeyCode::ControlRight))
+        || !keyboard_input.just_pressed(CONTROLS_SAVE_PALETTE)
+    {
+        return;
+    }
+
+    let saved = vlox_settings
+        .materials
+        .to_palette(vlox::PaletteFormat::Ron)
+        .map_err(|error| error.to_string())
+        .and_then(|bytes| {
+            std::fs::write(PALETTE_SAVE_PATH, bytes).map_err(|error| error.to_string())
+        });
+    match saved {
+        Ok(()) => info!("saved palette to {PALETTE_SAVE_PATH}"),
+        Err(error) => error!("couldn't save palette: {error}"),
+    }
+}
@@ -0,0 +1,1147 @@
+use bevy::prelude::*;
+
+use super::{
+    input::{Action, Binding, InputMap},
+    vlox::{
+        Clipboard, CsgOp, MaterialId, MaterialRule, Orientation, PruneRule, VloxData,
+        MAX_FLOOD_FILL, VOID,
+    },
+    VloxChanged, VloxSettings,
+};
+
+/// Vlox coordinates at the depth a tool is working at
+pub type Cell = (u128, u128, u128);
+
+/// Built-in tools and the registry other plugins add their tools to.
+pub struct ToolsPlugin;
+impl Plugin for ToolsPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_vlox_tool(PlaceTool)
+            .register_vlox_tool(EraseTool)
+            .register_vlox_tool(PaintTool::default())
+            .register_vlox_tool(ShapeTool::new(Shape::Box))
+            .register_vlox_tool(ShapeTool::new(Shape::Sphere))
+            .register_vlox_tool(ShapeTool::new(Shape::Line))
+            .register_vlox_tool(FloodFillTool)
+            .register_vlox_tool(ReplaceTool)
+            .register_vlox_tool(PruneTool::default())
+            .register_vlox_tool(SubdivideTool::default())
+            .register_vlox_tool(SelectTool::default())
+            .add_systems(Update, (select_tool, refresh_tool));
+    }
+}
+
+/// A change to a single vlox, made by a tool
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct VloxEdit {
+    pub cell: Cell,
+    pub depth: u8,
+    pub op: EditOp,
+}
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum EditOp {
+    /// Replaces the vlox, void removes it
+    Set(MaterialId),
+    /// Recolours the vlox without changing its shape, see `VloxData::paint`
+    Paint {
+        value: MaterialId,
+        surface_only: bool,
+    },
+    /// Fills the vlox and every face-connected vlox of the same material, see
+    /// `VloxData::flood_fill`
+    Fill(MaterialId),
+    /// Replaces one material with another everywhere, the cell is only where it was picked
+    Replace { old: MaterialId, new: MaterialId },
+    /// Collapses the detail inside the vlox into a single leaf, or everywhere into leaves at
+    /// the edit's depth, see `VloxData::prune_region`
+    Prune { rule: PruneRule, everywhere: bool },
+    /// Subdivides the vlox until its leaves are at this depth, without changing how it looks,
+    /// see `VloxData::refine_region`
+    Refine(u8),
+}
+impl EditOp {
+    /// True for edits that act on the whole model, wherever they were picked
+    pub fn is_global(&self) -> bool {
+        matches!(
+            self,
+            EditOp::Replace { .. }
+                | EditOp::Prune {
+                    everywhere: true,
+                    ..
+                }
+        )
+    }
+}
+impl VloxEdit {
+    pub fn set(cell: Cell, depth: u8, value: MaterialId) -> Self {
+        Self {
+            cell,
+            depth,
+            op: EditOp::Set(value),
+        }
+    }
+    /// Applies the edit, returning false if it was skipped
+    pub fn apply(&self, data: &mut VloxData) -> bool {
+        let (x, y, z) = self.cell;
+        match self.op {
+            EditOp::Set(value) => {
+                data.set(x, y, z, self.depth, value);
+                true
+            }
+            EditOp::Paint {
+                value,
+                surface_only,
+            } => data.paint(x, y, z, self.depth, value, surface_only),
+            EditOp::Fill(value) => match data.flood_fill(self.cell, self.depth, value) {
+                Some(filled) => !filled.is_empty(),
+                None => {
+                    warn!("flood fill stopped: region is larger than {MAX_FLOOD_FILL} vloxes");
+                    false
+                }
+            },
+            // replacing void would fill all the empty space
+            EditOp::Replace { old, new } if old == new || old == VOID => false,
+            EditOp::Replace { old, new } => {
+                data.replace_material(old, new);
+                true
+            }
+            EditOp::Prune { rule, everywhere } => {
+                let removed = if everywhere {
+                    data.prune_below(self.depth, rule)
+                } else {
+                    data.prune_region(self.cell, self.depth, self.depth, rule)
+                };
+                removed > 0
+            }
+            EditOp::Refine(depth) => data.refine_region(self.cell, self.depth, depth) > 0,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ToolButton {
+    /// The place action, the left mouse button by default
+    Primary,
+    /// The erase action, the right mouse button by default
+    Secondary,
+}
+
+/// What a tool can see of the editor when it is used
+pub struct ToolContext<'a> {
+    pub data: &'a VloxData,
+    /// The selected depth, which the cells below are at
+    pub depth: u8,
+    /// The selected material
+    pub material: MaterialId,
+    /// The vlox the crosshair is on
+    pub target: Option<Cell>,
+    /// Material of the leaf the crosshair hit, which can be smaller than the `target` vlox
+    pub hit_value: Option<MaterialId>,
+    /// The empty vlox in front of the targeted face, where new vloxes go
+    pub place: Option<Cell>,
+}
+impl ToolContext<'_> {
+    pub fn num_vlox(&self) -> u128 {
+        self.data.num_vlox(self.depth)
+    }
+}
+
+/// Outline drawn where a tool would act, in cells at the selected depth
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ToolPreview {
+    /// Every cell from `min` to `max` inclusive
+    Cells {
+        min: Cell,
+        max: Cell,
+    },
+    Sphere {
+        center: Cell,
+        radius: f32,
+    },
+}
+
+/// An editing tool, selected with its key and used with the mouse buttons.
+///
+/// Tools don't change the data themselves, they return the edits to make so that
+/// the editor can apply, remesh and relight them in one place.
+pub trait VloxTool: Send + Sync + 'static {
+    fn name(&self) -> &str;
+    fn key(&self) -> KeyCode;
+    fn color(&self) -> Color {
+        Color::WHITE
+    }
+    fn preview(&self, ctx: &ToolContext) -> Vec<ToolPreview>;
+    fn apply(&mut self, ctx: &ToolContext, button: ToolButton) -> Vec<VloxEdit>;
+    /// Forgets any half finished operation, called when another tool is switched to this one
+    fn reset(&mut self) {}
+    /// Handles the tool's own keys while it is active, called every frame
+    fn keys(
+        &mut self,
+        _ctx: &ToolContext,
+        _keyboard_input: &ButtonInput<KeyCode>,
+    ) -> Vec<VloxEdit> {
+        vec![]
+    }
+    /// Called when the tool is selected, and when the data, the selected depth or the targeted
+    /// vlox changes while it is active, for tools that show something worked out from them
+    fn refresh(&mut self, _data: &VloxData, _depth: u8, _target: Option<Cell>) {}
+    /// Switches between the tool's modes, if it has any
+    fn toggle_mode(&mut self) {}
+    /// The current mode, shown next to the tool name
+    fn mode(&self) -> Option<&str> {
+        None
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct ToolRegistry {
+    tools: Vec<Box<dyn VloxTool>>,
+    active: usize,
+}
+impl ToolRegistry {
+    pub fn register(&mut self, tool: impl VloxTool) {
+        self.tools.push(Box::new(tool));
+    }
+    pub fn active(&self) -> Option<&dyn VloxTool> {
+        self.tools.get(self.active).map(|tool| tool.as_ref())
+    }
+    pub fn active_mut(&mut self) -> Option<&mut Box<dyn VloxTool>> {
+        self.tools.get_mut(self.active)
+    }
+    /// Selects the tool `step` places after the active one, wrapping around
+    pub fn cycle(&mut self, step: isize) {
+        if self.tools.is_empty() {
+            return;
+        }
+        let count = self.tools.len() as isize;
+        self.active = (self.active as isize + step).rem_euclid(count) as usize;
+        self.tools[self.active].reset();
+    }
+    /// Switches to the named tool. Selecting the active tool again keeps what it is doing, so
+    /// its key can be reused inside it, like C while pasting with the select tool.
+    pub fn select(&mut self, name: &str) {
+        if let Some(i) = self.tools.iter().position(|tool| tool.name() == name) {
+            if i == self.active {
+                return;
+            }
+            self.active = i;
+            self.tools[i].reset();
+        }
+    }
+}
+
+/// Lets other plugins add their own tools
+pub trait RegisterVloxTool {
+    fn register_vlox_tool(&mut self, tool: impl VloxTool) -> &mut Self;
+}
+impl RegisterVloxTool for App {
+    fn register_vlox_tool(&mut self, tool: impl VloxTool) -> &mut Self {
+        self.world_mut()
+            .get_resource_or_insert_with(ToolRegistry::default)
+            .register(tool);
+        self
+    }
+}
+
+fn select_tool(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    (actions, input_map): (Res<ButtonInput<Action>>, Res<InputMap>),
+    mut tools: ResMut<ToolRegistry>,
+) {
+    // modifier shortcuts like Ctrl+P belong to other actions
+    if keyboard_input.any_pressed([
+        KeyCode::ControlLeft,
+        KeyCode::ControlRight,
+        KeyCode::AltLeft,
+        KeyCode::AltRight,
+    ]) {
+        return;
+    }
+    // keys bound to actions only do the action
+    let pressed = tools
+        .tools
+        .iter()
+        .filter(|tool| !input_map.is_bound(Binding::Key(tool.key())))
+        .find(|tool| keyboard_input.just_pressed(tool.key()))
+        .map(|tool| tool.name().to_string());
+    if let Some(name) = pressed {
+        tools.select(&name);
+    }
+    if actions.just_pressed(Action::NextTool) {
+        tools.cycle(1);
+    }
+    if actions.just_pressed(Action::PreviousTool) {
+        tools.cycle(-1);
+    }
+    if actions.just_pressed(Action::ToolMode) {
+        if let Some(tool) = tools.active_mut() {
+            tool.toggle_mode();
+        }
+    }
+}
+
+fn refresh_tool(
+    mut changes: EventReader<VloxChanged>,
+    vlox_settings: Res<VloxSettings>,
+    mut tools: ResMut<ToolRegistry>,
+    mut refreshed: Local<Option<(usize, u8, Option<Cell>)>>,
+) {
+    let changed = changes.read().count() > 0;
+    let depth = vlox_settings.selected_depth;
+    let target = vlox_settings.hovered;
+    let state = Some((tools.active, depth, target));
+    if !changed && *refreshed == state {
+        return;
+    }
+    *refreshed = state;
+    if let Some(tool) = tools.active_mut() {
+        tool.refresh(&vlox_settings.data, depth, target);
+    }
+}
+
+/// Draws tool previews, which are in cells at `depth`
+pub fn draw_previews(
+    gizmos: &mut Gizmos,
+    data: &VloxData,
+    depth: u8,
+    previews: &[ToolPreview],
+    color: Color,
+) {
+    let vlox_size = data.vlox_size(data.num_vlox(depth));
+    let center = |(x, y, z): Cell| Vec3::from(data.vlox_xyz_to_xyz_f32(x, y, z, depth));
+    for preview in previews {
+        match *preview {
+            ToolPreview::Cells { min, max } => {
+                let min = center(min) - Vec3::splat(vlox_size * 0.5);
+                let max = center(max) + Vec3::splat(vlox_size * 0.5);
+                gizmos.cuboid(
+                    Transform::from_translation((min + max) * 0.5).with_scale(max - min),
+                    color,
+                );
+            }
+            ToolPreview::Sphere {
+                center: cell,
+                radius,
+            } => {
+                gizmos.sphere(
+                    Isometry3d::from_translation(center(cell)),
+                    (radius + 0.5) * vlox_size,
+                    color,
+                );
+            }
+        }
+    }
+}
+
+struct PlaceTool;
+impl VloxTool for PlaceTool {
+    fn name(&self) -> &str {
+        "Place"
+    }
+    fn key(&self) -> KeyCode {
+        KeyCode::KeyB
+    }
+    fn preview(&self, ctx: &ToolContext) -> Vec<ToolPreview> {
+        ctx.place
+            .map(|cell| ToolPreview::Cells {
+                min: cell,
+                max: cell,
+            })
+            .into_iter()
+            .collect()
+    }
+    /// Right click removes, like the erase tool
+    fn apply(&mut self, ctx: &ToolContext, button: ToolButton) -> Vec<VloxEdit> {
+        let edit = match button {
+            ToolButton::Primary => ctx
+                .place
+                .map(|cell| VloxEdit::set(cell, ctx.depth, ctx.material)),
+            ToolButton::Secondary => ctx.target.map(|cell| VloxEdit::set(cell, ctx.depth, VOID)),
+        };
+        edit.into_iter().collect()
+    }
+}
+
+struct EraseTool;
+impl VloxTool for EraseTool {
+    fn name(&self) -> &str {
+        "Erase"
+    }
+    fn key(&self) -> KeyCode {
+        KeyCode::KeyE
+    }
+    fn color(&self) -> Color {
+        Color::srgb(1.0, 0.3, 0.3)
+    }
+    fn preview(&self, ctx: &ToolContext) -> Vec<ToolPreview> {
+        ctx.target
+            .map(|cell| ToolPreview::Cells {
+                min: cell,
+                max: cell,
+            })
+            .into_iter()
+            .collect()
+    }
+    fn apply(&mut self, ctx: &ToolContext, _button: ToolButton) -> Vec<VloxEdit> {
+        ctx.target
+            .map(|cell| VloxEdit::set(cell, ctx.depth, VOID))
+            .into_iter()
+            .collect()
+    }
+}
+
+/// Recolours the targeted vlox, keeping any finer detail inside it
+#[derive(Default)]
+struct PaintTool {
+    surface_only: bool,
+}
+impl VloxTool for PaintTool {
+    fn name(&self) -> &str {
+        "Paint"
+    }
+    fn key(&self) -> KeyCode {
+        KeyCode::KeyP
+    }
+    fn color(&self) -> Color {
+        Color::srgb(0.3, 0.8, 1.0)
+    }
+    fn preview(&self, ctx: &ToolContext) -> Vec<ToolPreview> {
+        EraseTool.preview(ctx)
+    }
+    fn apply(&mut self, ctx: &ToolContext, _button: ToolButton) -> Vec<VloxEdit> {
+        ctx.target
+            .map(|cell| VloxEdit {
+                cell,
+                depth: ctx.depth,
+                op: EditOp::Paint {
+                    value: ctx.material,
+                    surface_only: self.surface_only,
+                },
+            })
+            .into_iter()
+            .collect()
+    }
+    fn toggle_mode(&mut self) {
+        self.surface_only = !self.surface_only;
+    }
+    fn mode(&self) -> Option<&str> {
+        Some(if self.surface_only {
+            "surface only"
+        } else {
+            "all"
+        })
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Shape {
+    Box,
+    Sphere,
+    Line,
+}
+
+/// Two click tools: the first click sets the start, the second fills the shape.
+/// Left click builds onto faces, right click carves into the targeted vloxes.
+struct ShapeTool {
+    shape: Shape,
+    start: Option<(Cell, ToolButton)>,
+}
+impl ShapeTool {
+    fn new(shape: Shape) -> Self {
+        Self { shape, start: None }
+    }
+    fn cell(ctx: &ToolContext, button: ToolButton) -> Option<Cell> {
+        match button {
+            ToolButton::Primary => ctx.place,
+            ToolButton::Secondary => ctx.target,
+        }
+    }
+    fn cells(&self, start: Cell, end: Cell, num_vlox: u128) -> Vec<Cell> {
+        let (min, max) = bounds(start, end);
+        match self.shape {
+            Shape::Box => box_cells(min, max),
+            Shape::Sphere => {
+                let radius = distance(start, end);
+                let reach = radius.ceil() as u128;
+                let mut cells = vec![];
+                for x in start.0.saturating_sub(reach)..=(start.0 + reach).min(num_vlox - 1) {
+                    for y in start.1.saturating_sub(reach)..=(start.1 + reach).min(num_vlox - 1) {
+                        for z in start.2.saturating_sub(reach)..=(start.2 + reach).min(num_vlox - 1)
+                        {
+                            if distance(start, (x, y, z)) <= radius + 0.5 {
+                                cells.push((x, y, z));
+                            }
+                        }
+                    }
+                }
+                cells
+            }
+            Shape::Line => {
+                // one cell per step along the longest axis
+                let steps = [
+                    start.0.abs_diff(end.0),
+                    start.1.abs_diff(end.1),
+                    start.2.abs_diff(end.2),
+                ]
+                .into_iter()
+                .max()
+                .unwrap_or(0);
+                let lerp = |a: u128, b: u128, t: f32| {
+                    (a as f32 + (b as f32 - a as f32) * t).round() as u128
+                };
+                (0..=steps)
+                    .map(|step| {
+                        let t = if steps == 0 {
+                            0.0
+                        } else {
+                            step as f32 / steps as f32
+                        };
+                        (
+                            lerp(start.0, end.0, t),
+                            lerp(start.1, end.1, t),
+                            lerp(start.2, end.2, t),
+                        )
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+impl VloxTool for ShapeTool {
+    fn name(&self) -> &str {
+        match self.shape {
+            Shape::Box => "Box",
+            Shape::Sphere => "Sphere",
+            Shape::Line => "Line",
+        }
+    }
+    fn key(&self) -> KeyCode {
+        match self.shape {
+            Shape::Box => KeyCode::KeyX,
+            Shape::Sphere => KeyCode::KeyO,
+            Shape::Line => KeyCode::KeyL,
+        }
+    }
+    fn color(&self) -> Color {
+        Color::srgb(1.0, 0.9, 0.3)
+    }
+    fn preview(&self, ctx: &ToolContext) -> Vec<ToolPreview> {
+        let Some((start, button)) = self.start else {
+            return PlaceTool.preview(ctx);
+        };
+        let Some(end) = Self::cell(ctx, button) else {
+            return vec![ToolPreview::Cells {
+                min: start,
+                max: start,
+            }];
+        };
+        match self.shape {
+            Shape::Box => {
+                let (min, max) = bounds(start, end);
+                vec![ToolPreview::Cells { min, max }]
+            }
+            Shape::Sphere => vec![ToolPreview::Sphere {
+                center: start,
+                radius: distance(start, end),
+            }],
+            Shape::Line => self
+                .cells(start, end, ctx.num_vlox())
+                .into_iter()
+                .map(|cell| ToolPreview::Cells {
+                    min: cell,
+                    max: cell,
+                })
+                .collect(),
+        }
+    }
+    fn apply(&mut self, ctx: &ToolContext, button: ToolButton) -> Vec<VloxEdit> {
+        let Some(cell) = Self::cell(ctx, button) else {
+            return vec![];
+        };
+        match self.start.take() {
+            Some((start, start_button)) if start_button == button => {
+                let value = match button {
+                    ToolButton::Primary => ctx.material,
+                    ToolButton::Secondary => VOID,
+                };
+                self.cells(start, cell, ctx.num_vlox())
+                    .into_iter()
+                    .map(|cell| VloxEdit::set(cell, ctx.depth, value))
+                    .collect()
+            }
+            _ => {
+                self.start = Some((cell, button));
+                vec![]
+            }
+        }
+    }
+    fn reset(&mut self) {
+        self.start = None;
+    }
+}
+
+/// Replaces the targeted vlox, and every face-connected vlox of the same material, with the
+/// selected material
+struct FloodFillTool;
+impl VloxTool for FloodFillTool {
+    fn name(&self) -> &str {
+        "Flood Fill"
+    }
+    fn key(&self) -> KeyCode {
+        KeyCode::KeyG
+    }
+    fn color(&self) -> Color {
+        Color::srgb(0.3, 1.0, 0.5)
+    }
+    fn preview(&self, ctx: &ToolContext) -> Vec<ToolPreview> {
+        EraseTool.preview(ctx)
+    }
+    fn apply(&mut self, ctx: &ToolContext, _button: ToolButton) -> Vec<VloxEdit> {
+        ctx.target
+            .map(|cell| VloxEdit {
+                cell,
+                depth: ctx.depth,
+                op: EditOp::Fill(ctx.material),
+            })
+            .into_iter()
+            .collect()
+    }
+}
+
+/// Swaps the targeted vlox's material for the selected one, across the whole model
+struct ReplaceTool;
+impl VloxTool for ReplaceTool {
+    fn name(&self) -> &str {
+        "Replace"
+    }
+    fn key(&self) -> KeyCode {
+        KeyCode::KeyR
+    }
+    fn color(&self) -> Color {
+        Color::srgb(0.8, 0.4, 1.0)
+    }
+    fn preview(&self, ctx: &ToolContext) -> Vec<ToolPreview> {
+        EraseTool.preview(ctx)
+    }
+    /// Takes the material from the leaf that was hit, a vlox at the selected depth that is
+    /// mostly empty would read as void
+    fn apply(&mut self, ctx: &ToolContext, _button: ToolButton) -> Vec<VloxEdit> {
+        let (Some(cell), Some(old)) = (ctx.target, ctx.hit_value) else {
+            return vec![];
+        };
+        if old == VOID || old == ctx.material {
+            return vec![];
+        }
+        vec![VloxEdit {
+            cell,
+            depth: ctx.depth,
+            op: EditOp::Replace {
+                old,
+                new: ctx.material,
+            },
+        }]
+    }
+}
+
+/// The occupancy a vlox needs to stay solid when pruning with `PruneRule::Occupancy`
+const PRUNE_OCCUPANCY: f32 = 0.25;
+const PRUNE_RULES: [(&str, PruneRule); 2] = [
+    ("majority", PruneRule::Majority),
+    ("occupancy", PruneRule::Occupancy(PRUNE_OCCUPANCY)),
+];
+
+/// Collapses the detail inside the targeted vlox into a single vlox at the selected depth.
+/// Right clicking twice in a row clamps the whole model to the selected depth instead.
+#[derive(Default)]
+struct PruneTool {
+    /// Index into `PRUNE_RULES`
+    rule: usize,
+    /// Nodes pruning would remove in the targeted vlox, and everywhere
+    here: usize,
+    everywhere: usize,
+    /// Whether the next right click prunes everywhere
+    armed: bool,
+    /// The mode, with how many nodes each button would remove
+    label: String,
+}
+impl PruneTool {
+    fn update_label(&mut self) {
+        let rule = PRUNE_RULES[self.rule].0;
+        self.label = if self.armed {
+            format!(
+                "{rule}, right click again to remove {} everywhere",
+                self.everywhere
+            )
+        } else {
+            format!(
+                "{rule}, removes {} here, {} everywhere",
+                self.here, self.everywhere
+            )
+        };
+    }
+}
+impl VloxTool for PruneTool {
+    fn name(&self) -> &str {
+        "Prune"
+    }
+    fn key(&self) -> KeyCode {
+        KeyCode::KeyT
+    }
+    fn color(&self) -> Color {
+        Color::srgb(1.0, 0.6, 0.2)
+    }
+    fn preview(&self, ctx: &ToolContext) -> Vec<ToolPreview> {
+        EraseTool.preview(ctx)
+    }
+    fn apply(&mut self, ctx: &ToolContext, button: ToolButton) -> Vec<VloxEdit> {
+        let rule = PRUNE_RULES[self.rule].1;
+        // the first right click only asks for another
+        let everywhere = button == ToolButton::Secondary;
+        let confirmed = everywhere && self.armed;
+        self.armed = everywhere && !self.armed;
+        self.update_label();
+        let edit = match (everywhere, confirmed) {
+            (false, _) => ctx.target.map(|cell| VloxEdit {
+                cell,
+                depth: ctx.depth,
+                op: EditOp::Prune {
+                    rule,
+                    everywhere: false,
+                },
+            }),
+            (true, true) => Some(VloxEdit {
+                cell: ctx.target.unwrap_or((0, 0, 0)),
+                depth: ctx.depth,
+                op: EditOp::Prune {
+                    rule,
+                    everywhere: true,
+                },
+            }),
+            (true, false) => None,
+        };
+        edit.into_iter().collect()
+    }
+    fn reset(&mut self) {
+        self.armed = false;
+    }
+    /// Counts the nodes pruning would remove, when the data or the crosshair changes
+    fn refresh(&mut self, data: &VloxData, depth: u8, target: Option<Cell>) {
+        self.here = target.map_or(0, |cell| data.pruned_node_count(cell, depth, depth));
+        self.everywhere = data.pruned_node_count((0, 0, 0), 0, depth);
+        self.update_label();
+    }
+    fn toggle_mode(&mut self) {
+        self.rule = (self.rule + 1) % PRUNE_RULES.len();
+        self.update_label();
+    }
+    fn mode(&self) -> Option<&str> {
+        Some(&self.label)
+    }
+}
+
+/// How many levels `SubdivideTool` refines at once, each level multiplies the nodes by up to
+/// eight
+const REFINE_LEVELS: [(&str, u8); 3] = [("1 level", 1), ("2 levels", 2), ("3 levels", 3)];
+
+/// Splits the targeted vlox into eight children with its material, or refines it further,
+/// so the children can be edited at the next depth
+#[derive(Default)]
+struct SubdivideTool {
+    /// Index into `REFINE_LEVELS`
+    levels: usize,
+}
+impl VloxTool for SubdivideTool {
+    fn name(&self) -> &str {
+        "Subdivide"
+    }
+    fn key(&self) -> KeyCode {
+        KeyCode::KeyN
+    }
+    fn color(&self) -> Color {
+        Color::srgb(0.6, 1.0, 1.0)
+    }
+    fn preview(&self, ctx: &ToolContext) -> Vec<ToolPreview> {
+        EraseTool.preview(ctx)
+    }
+    fn apply(&mut self, ctx: &ToolContext, _button: ToolButton) -> Vec<VloxEdit> {
+        ctx.target
+            .map(|cell| VloxEdit {
+                cell,
+                depth: ctx.depth,
+                op: EditOp::Refine(ctx.depth + REFINE_LEVELS[self.levels].1),
+            })
+            .into_iter()
+            .collect()
+    }
+    fn toggle_mode(&mut self) {
+        self.levels = (self.levels + 1) % REFINE_LEVELS.len();
+    }
+    fn mode(&self) -> Option<&str> {
+        Some(REFINE_LEVELS[self.levels].0)
+    }
+}
+
+/// Copy, move and paste, keeping all the detail in the copied vloxes
+const CONTROLS_COPY: KeyCode = KeyCode::KeyC;
+const CONTROLS_PASTE: KeyCode = KeyCode::KeyV;
+const CONTROLS_COPY_MODIFIERS: [KeyCode; 2] = [KeyCode::ControlLeft, KeyCode::ControlRight];
+const CONTROLS_MOVE: KeyCode = KeyCode::KeyM;
+const CONTROLS_DELETE: KeyCode = KeyCode::Delete;
+/// Turn the paste a quarter about x, y or z, or mirror it along the axis with Shift held
+const CONTROLS_PASTE_AXES: [KeyCode; 3] = [KeyCode::KeyH, KeyCode::KeyJ, KeyCode::KeyK];
+const CONTROLS_PASTE_MIRROR_MODIFIERS: [KeyCode; 2] = [KeyCode::ShiftLeft, KeyCode::ShiftRight];
+/// Pasted material wins where a union or intersection overlaps solid vloxes
+const PASTE_MATERIAL_RULE: MaterialRule = MaterialRule::Replace;
+/// Ways of pasting, cycled with the mode key: plain replacement, then each boolean operation
+const PASTE_MODES: [Option<CsgOp>; 5] = [
+    None,
+    Some(CsgOp::Union),
+    Some(CsgOp::Subtract),
+    Some(CsgOp::Intersect),
+    Some(CsgOp::Xor),
+];
+/// Pastes with more cells than this only show their outline
+const MAX_GHOST_CELLS: usize = 4096;
+
+/// Box selection: left click two corners, then copy (Ctrl+C), move (M) or delete the selection.
+/// While pasting, the ghost follows the crosshair, left click pastes and right click stops.
+/// The mode key switches between replacing what is under the paste and combining with it.
+#[derive(Default)]
+struct SelectTool {
+    /// The selection's depth, and its first corner and second corner once it has one
+    selection: Option<(u8, Cell, Option<Cell>)>,
+    clipboard: Option<Clipboard>,
+    orientation: Orientation,
+    pasting: bool,
+    /// Index into `PASTE_MODES`
+    paste_mode: usize,
+    /// Cells of the turned clipboard, relative to its lowest corner
+    ghost: Vec<Cell>,
+}
+impl SelectTool {
+    fn selected(&self, depth: u8) -> Option<(Cell, Cell)> {
+        match self.selection {
+            Some((selected_depth, start, Some(end))) if selected_depth == depth => {
+                Some(bounds(start, end))
+            }
+            _ => None,
+        }
+    }
+    fn start_pasting(&mut self, clipboard: Clipboard) {
+        self.orientation = Orientation::default();
+        self.clipboard = Some(clipboard);
+        self.pasting = true;
+        self.update_ghost();
+    }
+    fn update_ghost(&mut self) {
+        self.ghost = self
+            .clipboard
+            .as_ref()
+            .map_or(vec![], |clipboard| clipboard.cells(self.orientation));
+    }
+    fn clear(min: Cell, max: Cell, depth: u8) -> Vec<VloxEdit> {
+        box_cells(min, max)
+            .into_iter()
+            .map(|cell| VloxEdit::set(cell, depth, VOID))
+            .collect()
+    }
+}
+impl VloxTool for SelectTool {
+    fn name(&self) -> &str {
+        "Select"
+    }
+    fn key(&self) -> KeyCode {
+        KeyCode::KeyC
+    }
+    fn color(&self) -> Color {
+        Color::srgb(0.4, 0.7, 1.0)
+    }
+    fn preview(&self, ctx: &ToolContext) -> Vec<ToolPreview> {
+        if self.pasting {
+            let (Some(clipboard), Some(at)) = (&self.clipboard, ctx.place) else {
+                return vec![];
+            };
+            let extent = self.orientation.extent(clipboard.extent());
+            let mut previews = vec![ToolPreview::Cells {
+                min: at,
+                max: (
+                    at.0 + extent.0 - 1,
+                    at.1 + extent.1 - 1,
+                    at.2 + extent.2 - 1,
+                ),
+            }];
+            if self.ghost.len() <= MAX_GHOST_CELLS {
+                previews.extend(self.ghost.iter().map(|cell| {
+                    let cell = (at.0 + cell.0, at.1 + cell.1, at.2 + cell.2);
+                    ToolPreview::Cells {
+                        min: cell,
+                        max: cell,
+                    }
+                }));
+            }
+            return previews;
+        }
+        match (self.selected(ctx.depth), self.selection, ctx.target) {
+            (Some((min, max)), _, _) => vec![ToolPreview::Cells { min, max }],
+            (None, Some((depth, start, None)), Some(target)) if depth == ctx.depth => {
+                let (min, max) = bounds(start, target);
+                vec![ToolPreview::Cells { min, max }]
+            }
+            _ => EraseTool.preview(ctx),
+        }
+    }
+    fn apply(&mut self, ctx: &ToolContext, button: ToolButton) -> Vec<VloxEdit> {
+        if self.pasting {
+            match (button, &self.clipboard, ctx.place) {
+                (ToolButton::Primary, Some(clipboard), Some(at)) => {
+                    let leaves = match PASTE_MODES[self.paste_mode] {
+                        Some(op) => clipboard.combined_leaves(
+                            ctx.data,
+                            self.orientation,
+                            at,
+                            ctx.depth,
+                            op,
+                            PASTE_MATERIAL_RULE,
+                        ),
+                        None => clipboard.leaves(self.orientation, at, ctx.depth),
+                    };
+                    return leaves
+                        .into_iter()
+                        .map(|(x, y, z, depth, value)| VloxEdit::set((x, y, z), depth, value))
+                        .collect();
+                }
+                (ToolButton::Secondary, _, _) => self.pasting = false,
+                _ => {}
+            }
+            return vec![];
+        }
+        match (button, ctx.target) {
+            (ToolButton::Primary, Some(target)) => {
+                self.selection = match self.selection {
+                    Some((depth, start, None)) if depth == ctx.depth => {
+                        Some((depth, start, Some(target)))
+                    }
+                    _ => Some((ctx.depth, target, None)),
+                };
+            }
+            (ToolButton::Secondary, _) => self.selection = None,
+            _ => {}
+        }
+        vec![]
+    }
+    fn keys(&mut self, ctx: &ToolContext, keyboard_input: &ButtonInput<KeyCode>) -> Vec<VloxEdit> {
+        let modified = keyboard_input.any_pressed(CONTROLS_COPY_MODIFIERS);
+        if self.pasting {
+            let mirror = keyboard_input.any_pressed(CONTROLS_PASTE_MIRROR_MODIFIERS);
+            for (axis, key) in CONTROLS_PASTE_AXES.into_iter().enumerate() {
+                if keyboard_input.just_pressed(key) {
+                    self.orientation = if mirror {
+                        self.orientation.mirrored(axis)
+                    } else {
+                        self.orientation.rotated(axis)
+                    };
+                    self.update_ghost();
+                }
+            }
+            return vec![];
+        }
+        if modified && keyboard_input.just_pressed(CONTROLS_PASTE) && self.clipboard.is_some() {
+            self.pasting = true;
+            self.orientation = Orientation::default();
+            self.update_ghost();
+            return vec![];
+        }
+
+        let Some((min, max)) = self.selected(ctx.depth) else {
+            return vec![];
+        };
+        if modified && keyboard_input.just_pressed(CONTROLS_COPY) {
+            self.start_pasting(ctx.data.copy_region(min, max, ctx.depth));
+        } else if keyboard_input.just_pressed(CONTROLS_MOVE) {
+            self.start_pasting(ctx.data.copy_region(min, max, ctx.depth));
+            self.selection = None;
+            return Self::clear(min, max, ctx.depth);
+        } else if keyboard_input.just_pressed(CONTROLS_DELETE) {
+            self.selection = None;
+            return Self::clear(min, max, ctx.depth);
+        }
+        vec![]
+    }
+    /// Stops pasting, but keeps the selection and the clipboard
+    fn reset(&mut self) {
+        self.pasting = false;
+    }
+    fn toggle_mode(&mut self) {
+        self.paste_mode = (self.paste_mode + 1) % PASTE_MODES.len();
+    }
+    fn mode(&self) -> Option<&str> {
+        Some(if self.pasting {
+            match PASTE_MODES[self.paste_mode] {
+                None => "pasting",
+                Some(CsgOp::Union) => "pasting, union",
+                Some(CsgOp::Subtract) => "pasting, subtract",
+                Some(CsgOp::Intersect) => "pasting, intersect",
+                Some(CsgOp::Xor) => "pasting, xor",
+            }
+        } else if matches!(self.selection, Some((_, _, Some(_)))) {
+            "selected"
+        } else {
+            "selecting"
+        })
+    }
+}
+
+/// Every cell from `min` to `max` inclusive
+fn box_cells(min: Cell, max: Cell) -> Vec<Cell> {
+    let mut cells = vec![];
+    for x in min.0..=max.0 {
+        for y in min.1..=max.1 {
+            for z in min.2..=max.2 {
+                cells.push((x, y, z));
+            }
+        }
+    }
+    cells
+}
+
+fn bounds(a: Cell, b: Cell) -> (Cell, Cell) {
+    (
+        (a.0.min(b.0), a.1.min(b.1), a.2.min(b.2)),
+        (a.0.max(b.0), a.1.max(b.1), a.2.max(b.2)),
+    )
+}
+
+fn distance(a: Cell, b: Cell) -> f32 {
+    let d = |a: u128, b: u128| a.abs_diff(b) as f32;
+    (d(a.0, b.0).powi(2) + d(a.1, b.1).powi(2) + d(a.2, b.2).powi(2)).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replace(data: &VloxData, hit_value: MaterialId) -> Vec<VloxEdit> {
+        let ctx = ToolContext {
+            data,
+            depth: 1,
+            material: 2,
+            target: Some((0, 0, 0)),
+            hit_value: Some(hit_value),
+            place: None,
+        };
+        ReplaceTool.apply(&ctx, ToolButton::Primary)
+    }
+
+    #[test]
+    fn replace_takes_the_material_from_the_hit_leaf() {
+        let mut data = VloxData::new(2);
+        // one vlox at depth 3 in an otherwise empty depth 1 vlox, which reads as void
+        data.set(0, 0, 0, 3, 1);
+        assert_eq!(VOID, data.get(0, 0, 0, 1));
+
+        let edits = replace(&data, 1);
+        assert_eq!(
+            vec![VloxEdit {
+                cell: (0, 0, 0),
+                depth: 1,
+                op: EditOp::Replace { old: 1, new: 2 },
+            }],
+            edits
+        );
+        for edit in &edits {
+            assert!(edit.apply(&mut data));
+        }
+        assert_eq!(2, data.get(0, 0, 0, 3));
+        assert_eq!(VOID, data.get(1, 0, 0, 3));
+        assert_eq!(VOID, data.get(1, 1, 1, 1));
+
+        // nothing to do when the hit leaf already has the material, and void is never replaced
+        assert!(replace(&data, 2).is_empty());
+        assert!(replace(&data, VOID).is_empty());
+        let replace_void = VloxEdit {
+            cell: (0, 0, 0),
+            depth: 1,
+            op: EditOp::Replace { old: VOID, new: 2 },
+        };
+        assert!(!replace_void.apply(&mut data));
+        assert_eq!(VOID, data.get(1, 1, 1, 1));
+    }
+
+    #[test]
+    fn pruning_everywhere_needs_a_second_right_click() {
+        let mut data = VloxData::new(2);
+        data.set(0, 0, 0, 3, 1);
+        data.set(7, 7, 7, 3, 1);
+        let ctx = ToolContext {
+            data: &data,
+            depth: 1,
+            material: 1,
+            target: Some((0, 0, 0)),
+            hit_value: Some(1),
+            place: None,
+        };
+        let mut tool = PruneTool::default();
+        tool.refresh(&data, 1, Some((0, 0, 0)));
+        assert_eq!(Some("majority, removes 2 here, 4 everywhere"), tool.mode());
+
+        assert!(tool.apply(&ctx, ToolButton::Secondary).is_empty());
+        assert_eq!(
+            Some("majority, right click again to remove 4 everywhere"),
+            tool.mode()
+        );
+        let edits = tool.apply(&ctx, ToolButton::Secondary);
+        assert!(matches!(
+            edits[..],
+            [VloxEdit {
+                op: EditOp::Prune {
+                    everywhere: true,
+                    ..
+                },
+                ..
+            }]
+        ));
+
+        // anything else in between starts again
+        tool.apply(&ctx, ToolButton::Secondary);
+        tool.apply(&ctx, ToolButton::Primary);
+        assert!(tool.apply(&ctx, ToolButton::Secondary).is_empty());
+        tool.reset();
+        assert!(tool.apply(&ctx, ToolButton::Secondary).is_empty());
+    }
+
+    #[test]
+    fn selecting_the_active_tool_keeps_its_state() {
+        let mut tools = ToolRegistry::default();
+        tools.register(PlaceTool);
+        tools.register(SelectTool::default());
+        tools.select("Select");
+        let pasting =
+            |tools: &ToolRegistry| tools.active().and_then(|tool| tool.mode()) == Some("pasting");
+
+        let mut data = VloxData::new(2);
+        data.set(0, 0, 0, 2, 1);
+        let ctx = ToolContext {
+            data: &data,
+            depth: 2,
+            material: 1,
+            target: Some((0, 0, 0)),
+            hit_value: Some(1),
+            place: None,
+        };
+        let tool = tools.active_mut().unwrap();
+        tool.apply(&ctx, ToolButton::Primary);
+        tool.apply(&ctx, ToolButton::Primary);
+        let mut keyboard_input = ButtonInput::default();
+        keyboard_input.press(KeyCode::ControlLeft);
+        keyboard_input.press(KeyCode::KeyC);
+        tool.keys(&ctx, &keyboard_input);
+        assert!(pasting(&tools));
+
+        // C is also the select tool's key
+        tools.select("Select");
+        assert!(pasting(&tools));
+        tools.select("Place");
+        tools.select("Select");
+        assert!(!pasting(&tools));
+    }
+}
@@ -0,0 +1,482 @@
+use bevy::{input::touch::TouchPhase, prelude::*};
+use bevy_flycam::FlyCam;
+
+use super::{
+    gamepad::fly,
+    input::{Action, UpdateActions},
+};
+
+/// Touches starting in this fraction of the screen on the left control the joystick
+const JOYSTICK_AREA: f32 = 1.0 / 3.0;
+/// Pixels a finger has to move from where it started to push the joystick all the way
+const JOYSTICK_RADIUS: f32 = 60.0;
+/// World units moved per second with the joystick pushed all the way
+const TOUCH_MOVE_SPEED: f32 = 6.0;
+/// Radians turned per pixel dragged
+const TOUCH_LOOK_SENSITIVITY: f32 = 0.005;
+/// Fingers that move less than this many pixels are tapping or pressing, not looking around
+const TAP_SLOP: f32 = 12.0;
+/// Seconds a finger has to stay down to erase instead of place
+const LONG_PRESS: f32 = 0.5;
+
+/// Size of the on-screen depth buttons, and their gap from each other and the screen edge
+const DEPTH_BUTTON_SIZE: f32 = 64.0;
+const DEPTH_BUTTON_GAP: f32 = 16.0;
+const DEPTH_BUTTON_COLOR: Color = Color::srgba(0.0, 0.0, 0.0, 0.5);
+const JOYSTICK_COLOR: Color = Color::srgba(1.0, 1.0, 1.0, 0.2);
+
+/// Touch controls for phones: a joystick on the left of the screen moves, dragging anywhere
+/// else looks around, a tap places and a long press erases where the finger is. Buttons on
+/// the right change the depth. They are driven by `TouchInput` events, and shown from the
+/// first touch.
+pub struct TouchControlsPlugin;
+impl Plugin for TouchControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TouchControls>()
+            .add_systems(Startup, spawn_touch_ui)
+            .add_systems(PreUpdate, touch_controls.after(UpdateActions))
+            .add_systems(Update, update_touch_ui);
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct TouchControls {
+    /// Set by the first touch. Touch devices don't lock the pointer or go fullscreen.
+    pub active: bool,
+    /// Where the last tap or long press was, in window coordinates
+    tap: Option<Vec2>,
+    joystick: Option<Joystick>,
+    /// Fingers that are looking around, tapping or long pressing
+    fingers: Vec<Finger>,
+}
+
+struct Joystick {
+    id: u64,
+    start: Vec2,
+    position: Vec2,
+}
+impl Joystick {
+    /// How far it is pushed each way, up to 1, with up positive
+    fn value(&self) -> Vec2 {
+        let offset = (self.position - self.start) / JOYSTICK_RADIUS;
+        Vec2::new(offset.x, -offset.y).clamp_length_max(1.0)
+    }
+}
+
+struct Finger {
+    id: u64,
+    start: Vec2,
+    last: Vec2,
+    /// Seconds since startup when it touched down
+    time: f32,
+    /// Whether it moved too far to be a tap or long press
+    dragged: bool,
+    /// Whether the long press already erased
+    long_pressed: bool,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum DepthButton {
+    Up,
+    Down,
+}
+impl DepthButton {
+    fn action(self) -> Action {
+        match self {
+            DepthButton::Up => Action::VloxSizeUp,
+            DepthButton::Down => Action::VloxSizeDown,
+        }
+    }
+    /// The button under a position on a window of this size, stacked at the middle right
+    fn at(position: Vec2, window: Vec2) -> Option<Self> {
+        let right = window.x - DEPTH_BUTTON_GAP;
+        let left = right - DEPTH_BUTTON_SIZE;
+        let middle = window.y * 0.5;
+        if position.x < left || position.x > right {
+            return None;
+        }
+        let above = middle - DEPTH_BUTTON_GAP * 0.5 - position.y;
+        let below = position.y - middle - DEPTH_BUTTON_GAP * 0.5;
+        if (0.0..=DEPTH_BUTTON_SIZE).contains(&above) {
+            Some(DepthButton::Up)
+        } else if (0.0..=DEPTH_BUTTON_SIZE).contains(&below) {
+            Some(DepthButton::Down)
+        } else {
+            None
+        }
+    }
+}
+
+fn touch_controls(
+    mut touch_input: EventReader<TouchInput>,
+    time: Res<Time>,
+    window: Single<&Window>,
+    mut cameras: Query<&mut Transform, With<FlyCam>>,
+    mut actions: ResMut<ButtonInput<Action>>,
+    mut touch: ResMut<TouchControls>,
+) {
+    let now = time.elapsed_secs();
+    let mut turn = Vec2::ZERO;
+    for event in touch_input.read() {
+        touch.active = true;
+        let (id, position) = (event.id, event.position);
+        match event.phase {
+            TouchPhase::Started => {
+                if let Some(button) = DepthButton::at(position, window.size()) {
+                    actions.press(button.action());
+                } else if touch.joystick.is_none() && position.x < window.width() * JOYSTICK_AREA {
+                    touch.joystick = Some(Joystick {
+                        id,
+                        start: position,
+                        position,
+                    });
+                } else {
+                    touch.fingers.push(Finger {
+                        id,
+                        start: position,
+                        last: position,
+                        time: now,
+                        dragged: false,
+                        long_pressed: false,
+                    });
+                }
+            }
+            TouchPhase::Moved => {
+                if let Some(joystick) = touch.joystick.as_mut().filter(|stick| stick.id == id) {
+                    joystick.position = position;
+                } else if let Some(finger) = touch.fingers.iter_mut().find(|f| f.id == id) {
+                    finger.dragged |= finger.start.distance(position) > TAP_SLOP;
+                    if finger.dragged {
+                        // screen y grows downwards, dragging up looks up
+                        let delta = position - finger.last;
+                        turn += Vec2::new(delta.x, -delta.y) * TOUCH_LOOK_SENSITIVITY;
+                    }
+                    finger.last = position;
+                }
+            }
+            TouchPhase::Ended | TouchPhase::Canceled => {
+                if touch.joystick.as_ref().is_some_and(|stick| stick.id == id) {
+                    touch.joystick = None;
+                }
+                let Some(i) = touch.fingers.iter().position(|f| f.id == id) else {
+                    continue;
+                };
+                let finger = touch.fingers.remove(i);
+                let tapped = event.phase == TouchPhase::Ended
+                    && !finger.dragged
+                    && !finger.long_pressed
+                    && now - finger.time < LONG_PRESS;
+                if tapped {
+                    touch.tap = Some(position);
+                    actions.press(Action::Place);
+                }
+            }
+        }
+    }
+
+    let mut long_pressed = None;
+    for finger in touch.fingers.iter_mut() {
+        if !finger.dragged && !finger.long_pressed && now - finger.time >= LONG_PRESS {
+            finger.long_pressed = true;
+            long_pressed = Some(finger.last);
+            actions.press(Action::Erase);
+        }
+    }
+    if long_pressed.is_some() {
+        touch.tap = long_pressed;
+    }
+
+    let movement = touch
+        .joystick
+        .as_ref()
+        .map_or(Vec2::ZERO, |joystick| joystick.value());
+    if let Ok(mut camera) = cameras.get_single_mut() {
+        fly(
+            &mut camera,
+            movement * TOUCH_MOVE_SPEED * time.delta_secs(),
+            turn,
+        );
+    }
+}
+
+/// The ray edits aim along: straight ahead through the crosshair, or through the last tap
+/// once the touch controls are in use, as a touch screen has nothing to aim the crosshair with
+pub fn aim(touch: &TouchControls, camera: &Camera, transform: &Transform) -> Ray3d {
+    let transform = GlobalTransform::from(*transform);
+    touch
+        .tap
+        .filter(|_| touch.active)
+        .and_then(|tap| camera.viewport_to_world(&transform, tap).ok())
+        .unwrap_or(Ray3d::new(transform.translation(), transform.forward()))
+}
+
+#[derive(Component)]
+struct TouchUi;
+
+#[derive(Component)]
+struct JoystickBase;
+
+fn spawn_touch_ui(mut commands: Commands) {
+    for (label, button) in [("+", DepthButton::Up), ("-", DepthButton::Down)] {
+        // matches the layout `DepthButton::at` expects
+        let offset = match button {
+            DepthButton::Up => -DEPTH_BUTTON_SIZE - DEPTH_BUTTON_GAP * 0.5,
+            DepthButton::Down => DEPTH_BUTTON_GAP * 0.5,
+        };
+        commands
+            .spawn((
+                Node {
+                    position_type: PositionType::Absolute,
+                    right: Val::Px(DEPTH_BUTTON_GAP),
+                    top: Val::Percent(50.0),
+                    margin: UiRect::top(Val::Px(offset)),
+                    width: Val::Px(DEPTH_BUTTON_SIZE),
+                    height: Val::Px(DEPTH_BUTTON_SIZE),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                BackgroundColor(DEPTH_BUTTON_COLOR),
+                Visibility::Hidden,
+                TouchUi,
+            ))
+            .with_child((Text::new(label), TextFont::from_font_size(32.0)));
+    }
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Px(JOYSTICK_RADIUS * 2.0),
+            height: Val::Px(JOYSTICK_RADIUS * 2.0),
+            ..default()
+        },
+        BorderRadius::MAX,
+        BackgroundColor(JOYSTICK_COLOR),
+        Visibility::Hidden,
+        JoystickBase,
+    ));
+}
+
+fn update_touch_ui(
+    touch: Res<TouchControls>,
+    mut buttons: Query<&mut Visibility, (With<TouchUi>, Without<JoystickBase>)>,
+    joystick: Single<(&mut Node, &mut Visibility), With<JoystickBase>>,
+) {
+    if !touch.is_changed() {
+        return;
+    }
+    for mut visibility in buttons.iter_mut() {
+        *visibility = if touch.active {
+            Visibility::Visible
+        } else {
+            Visibility::Hidden
+        };
+    }
+
+    let (mut node, mut visibility) = joystick.into_inner();
+    match &touch.joystick {
+        Some(stick) => {
+            node.left = Val::Px(stick.start.x - JOYSTICK_RADIUS);
+            node.top = Val::Px(stick.start.y - JOYSTICK_RADIUS);
+            *visibility = Visibility::Visible;
+        }
+        None => *visibility = Visibility::Hidden,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::{
+        render::camera::{camera_system, ManualTextureViews},
+        time::TimeUpdateStrategy,
+        window::{PrimaryWindow, WindowCreated, WindowResized, WindowScaleFactorChanged},
+    };
+
+    use super::super::input::{InputMap, InputPlugin};
+    use super::*;
+
+    /// A headless app with a window, a camera, the input map and the touch controls
+    fn app() -> (App, Entity) {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            bevy::input::InputPlugin,
+            InputPlugin,
+            TouchControlsPlugin,
+        ))
+        // ignore any input map saved in the working directory
+        .insert_resource(InputMap::default())
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+            100,
+        )))
+        // what the camera needs to know its viewport
+        .add_event::<WindowCreated>()
+        .add_event::<WindowResized>()
+        .add_event::<WindowScaleFactorChanged>()
+        .add_event::<AssetEvent<Image>>()
+        .init_resource::<Assets<Image>>()
+        .init_resource::<ManualTextureViews>()
+        .add_systems(PostUpdate, camera_system::<Projection>);
+        let window = app
+            .world_mut()
+            .spawn((Window::default(), PrimaryWindow))
+            .id();
+        app.world_mut()
+            .spawn((Camera3d::default(), Transform::default(), FlyCam));
+        app.update();
+        (app, window)
+    }
+
+    fn touch(app: &mut App, window: Entity, id: u64, phase: TouchPhase, position: Vec2) {
+        app.world_mut().send_event(TouchInput {
+            phase,
+            position,
+            window,
+            force: None,
+            id,
+        });
+    }
+
+    fn size(app: &mut App) -> Vec2 {
+        app.world_mut()
+            .query::<&Window>()
+            .single(app.world())
+            .size()
+    }
+
+    fn aimed(app: &mut App) -> Ray3d {
+        let (camera, transform) = app
+            .world_mut()
+            .query::<(&Camera, &Transform)>()
+            .single(app.world());
+        aim(app.world().resource::<TouchControls>(), camera, transform)
+    }
+
+    fn camera(app: &mut App) -> Transform {
+        *app.world_mut()
+            .query_filtered::<&Transform, With<FlyCam>>()
+            .single(app.world())
+    }
+
+    #[test]
+    fn taps_place_and_long_presses_erase() {
+        let (mut app, window) = app();
+        let center = size(&mut app) * 0.5;
+        touch(&mut app, window, 0, TouchPhase::Started, center);
+        app.update();
+        assert!(app.world().resource::<TouchControls>().active);
+        touch(&mut app, window, 0, TouchPhase::Ended, center);
+        app.update();
+        let actions = app.world().resource::<ButtonInput<Action>>();
+        assert!(actions.just_pressed(Action::Place));
+        assert!(!actions.pressed(Action::Erase));
+
+        touch(&mut app, window, 1, TouchPhase::Started, center);
+        let mut erased = 0;
+        for _ in 0..8 {
+            app.update();
+            let actions = app.world().resource::<ButtonInput<Action>>();
+            erased += actions.just_pressed(Action::Erase) as usize;
+            assert!(!actions.pressed(Action::Place));
+        }
+        assert_eq!(1, erased);
+        // letting go after a long press doesn't place as well
+        touch(&mut app, window, 1, TouchPhase::Ended, center);
+        app.update();
+        let actions = app.world().resource::<ButtonInput<Action>>();
+        assert!(!actions.pressed(Action::Place));
+    }
+
+    #[test]
+    fn taps_aim_where_they_touch() {
+        let (mut app, window) = app();
+        let size = size(&mut app);
+        // before any touch, edits aim through the crosshair
+        assert_eq!(Vec3::NEG_Z, *aimed(&mut app).direction);
+
+        let up_right = Vec2::new(size.x * 0.75, size.y * 0.25);
+        touch(&mut app, window, 0, TouchPhase::Started, up_right);
+        touch(&mut app, window, 0, TouchPhase::Ended, up_right);
+        app.update();
+        let ray = aimed(&mut app);
+        // from the near plane, just in front of the camera
+        assert!(ray.origin.length() < 1.0);
+        // up and to the right of the centre, still looking ahead
+        assert!(ray.direction.x > 0.0 && ray.direction.y > 0.0 && ray.direction.z < 0.0);
+
+        let center = size * 0.5;
+        touch(&mut app, window, 1, TouchPhase::Started, center);
+        for _ in 0..6 {
+            app.update();
+        }
+        assert!(aimed(&mut app).direction.distance(Vec3::NEG_Z) < 1e-5);
+    }
+
+    #[test]
+    fn depth_buttons_press_their_actions() {
+        let (mut app, window) = app();
+        let size = size(&mut app);
+        let x = size.x - DEPTH_BUTTON_GAP - DEPTH_BUTTON_SIZE * 0.5;
+        let up = Vec2::new(x, size.y * 0.5 - DEPTH_BUTTON_SIZE * 0.5);
+        touch(&mut app, window, 0, TouchPhase::Started, up);
+        app.update();
+        let actions = app.world().resource::<ButtonInput<Action>>();
+        assert!(actions.just_pressed(Action::VloxSizeUp));
+        assert!(!actions.pressed(Action::VloxSizeDown));
+        touch(&mut app, window, 0, TouchPhase::Ended, up);
+        app.update();
+        // pressing a button isn't a tap
+        let actions = app.world().resource::<ButtonInput<Action>>();
+        assert!(!actions.pressed(Action::Place));
+    }
+
+    #[test]
+    fn joystick_moves_and_dragging_looks() {
+        let (mut app, window) = app();
+        let size = size(&mut app);
+        let start = camera(&mut app);
+
+        let stick = Vec2::new(size.x * 0.1, size.y * 0.8);
+        touch(&mut app, window, 0, TouchPhase::Started, stick);
+        touch(
+            &mut app,
+            window,
+            0,
+            TouchPhase::Moved,
+            stick - Vec2::Y * JOYSTICK_RADIUS,
+        );
+        app.update();
+        let moved = camera(&mut app);
+        // pushing up moves forward, which is -z
+        assert!(moved.translation.z < start.translation.z);
+        assert_eq!(start.rotation, moved.rotation);
+        touch(&mut app, window, 0, TouchPhase::Ended, stick);
+        app.update();
+
+        let drag = size * 0.5;
+        let stopped = camera(&mut app);
+        touch(&mut app, window, 1, TouchPhase::Started, drag);
+        touch(
+            &mut app,
+            window,
+            1,
+            TouchPhase::Moved,
+            drag + Vec2::X * 100.0,
+        );
+        touch(
+            &mut app,
+            window,
+            1,
+            TouchPhase::Ended,
+            drag + Vec2::X * 100.0,
+        );
+        app.update();
+        let turned = camera(&mut app);
+        assert_eq!(stopped.translation, turned.translation);
+        // dragging right turns right
+        assert!(turned.forward().x > 0.0);
+        let actions = app.world().resource::<ButtonInput<Action>>();
+        assert!(!actions.pressed(Action::Place));
+    }
+}
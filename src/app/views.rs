@@ -0,0 +1,352 @@
+use bevy::{
+    input::mouse::AccumulatedMouseMotion, prelude::*, render::camera::ScalingMode,
+    window::CursorGrabMode,
+};
+use bevy_flycam::FlyCam;
+
+use super::{orbit::OrbitCamera, tools::Cell, vlox::VloxData, VloxSettings};
+
+/// Numpad style presets looking from the front, right and top, with Ctrl from the back, left
+/// and bottom instead
+const CONTROLS_VIEW_FRONT: KeyCode = KeyCode::Numpad1;
+const CONTROLS_VIEW_RIGHT: KeyCode = KeyCode::Numpad3;
+const CONTROLS_VIEW_TOP: KeyCode = KeyCode::Numpad7;
+const CONTROLS_VIEW_OPPOSITE_MODIFIERS: [KeyCode; 2] =
+    [KeyCode::ControlLeft, KeyCode::ControlRight];
+/// Goes back to the perspective fly camera
+const CONTROLS_VIEW_PERSPECTIVE: KeyCode = KeyCode::Numpad5;
+const CONTROLS_VIEW_ZOOM_IN: KeyCode = KeyCode::NumpadAdd;
+const CONTROLS_VIEW_ZOOM_OUT: KeyCode = KeyCode::NumpadSubtract;
+/// Move the working plane towards and away from the camera by one vlox of the selected depth
+const CONTROLS_PLANE_NEARER: KeyCode = KeyCode::Numpad8;
+const CONTROLS_PLANE_FURTHER: KeyCode = KeyCode::Numpad2;
+
+/// How much of the object fits in the view, as a multiple of its size
+const VIEW_MARGIN: f32 = 1.5;
+const ZOOM_STEP: f32 = 0.8;
+/// Fraction of the view height panned per pixel of mouse movement
+const PAN_SENSITIVITY: f32 = 0.002;
+/// How far in front of the working plane the camera is, as a multiple of the object size
+const CAMERA_DISTANCE: f32 = 2.0;
+/// The working plane is snapped to the grid, so within this fraction of a vlox of a boundary
+/// between vloxes it is on that boundary
+const PLANE_GRID_EPSILON: f32 = 1e-3;
+
+const GRID_COLOR: Color = Color::srgba(1.0, 1.0, 1.0, 0.25);
+
+/// Orthographic views along the axes, with a grid on a working plane that edits can be placed
+/// against where there is nothing else to hit
+pub struct AxisViewsPlugin;
+impl Plugin for AxisViewsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AxisViews>()
+            .add_systems(Update, ((select_view, control_view).chain(), draw_grid));
+    }
+}
+
+/// The direction an axis view looks in
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AxisView {
+    /// 0, 1, 2 for x, y, z
+    pub axis: usize,
+    /// Whether it looks along the positive direction of the axis
+    pub positive: bool,
+}
+impl AxisView {
+    fn forward(&self) -> Vec3 {
+        let mut forward = [0.0; 3];
+        forward[self.axis] = if self.positive { 1.0 } else { -1.0 };
+        forward.into()
+    }
+    fn up(&self) -> Vec3 {
+        if self.axis == 1 {
+            Vec3::Z
+        } else {
+            Vec3::Y
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct AxisViews {
+    /// None in the perspective view
+    pub view: Option<AxisView>,
+    /// Point the view is centred on, panned across the screen
+    center: Vec3,
+    /// Height of the view in world units
+    height: f32,
+    /// Position of the working plane along the view's axis
+    plane: f32,
+}
+impl AxisViews {
+    /// Where placing against the working plane along a ray puts a vlox at `depth`, and how far
+    /// along the ray the plane is. The vlox is the one on the camera's side of the plane, None
+    /// if that is outside the data. None if it isn't in an axis view or the plane is behind.
+    pub fn place_on_plane(
+        &self,
+        data: &VloxData,
+        origin: Vec3,
+        direction: Vec3,
+        depth: u8,
+    ) -> Option<(Option<Cell>, f32)> {
+        let (point, distance) = self.on_plane(origin, direction)?;
+        let axis = self.view?.axis;
+        let half = data.size() * 0.5;
+        let inside = (0..3).all(|i| i == axis || point[i].abs() < half);
+        // across the plane, count vloxes from the plane's position rather than nudging the
+        // point, which can cross it at deep depths
+        let num_vlox = data.num_vlox(depth);
+        let plane = (self.plane + half) / data.vlox_size(num_vlox);
+        let boundary = plane.round();
+        let index = if (plane - boundary).abs() < PLANE_GRID_EPSILON {
+            // on a boundary between vloxes, the one on the camera's side
+            (boundary as u128).checked_sub((direction[axis] > 0.0) as u128)
+        } else {
+            Some(plane.floor() as u128)
+        };
+        let cell = index
+            .filter(|index| inside && *index < num_vlox)
+            .map(|index| {
+                let (x, y, z) = data.xyz_f32_to_vlox_xyz(point.x, point.y, point.z, depth);
+                let mut cell = [x, y, z];
+                cell[axis] = index;
+                (cell[0], cell[1], cell[2])
+            });
+        Some((cell, distance))
+    }
+    /// Where a ray hits the working plane, if it is in an axis view and the plane is in front
+    fn on_plane(&self, origin: Vec3, direction: Vec3) -> Option<(Vec3, f32)> {
+        let view = self.view?;
+        let axis = view.axis;
+        if direction[axis] == 0.0 {
+            return None;
+        }
+        let distance = (self.plane - origin[axis]) / direction[axis];
+        (distance > 0.0).then(|| (origin + direction * distance, distance))
+    }
+    /// Moves the working plane `steps` vloxes of size `step` towards the camera, snapped to
+    /// their grid. It stays inside data of size `size`, with room on the camera's side to
+    /// place a vlox against it.
+    fn move_plane(&mut self, view: AxisView, steps: i32, step: f32, size: f32) {
+        let half = size * 0.5;
+        let towards_camera = -view.forward()[view.axis];
+        let plane = ((self.plane / step).round() + steps as f32 * towards_camera) * step;
+        let (min, max) = if towards_camera > 0.0 {
+            (-half, half - step)
+        } else {
+            (-half + step, half)
+        };
+        self.plane = plane.clamp(min, max);
+    }
+    fn transform(&self, view: AxisView, size: f32) -> Transform {
+        let forward = view.forward();
+        Transform::from_translation(self.center - forward * size * CAMERA_DISTANCE)
+            .looking_to(forward, view.up())
+    }
+}
+
+fn select_view(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    camera: Single<(Entity, &mut Projection), With<Camera>>,
+    vlox_settings: Res<VloxSettings>,
+    mut views: ResMut<AxisViews>,
+    mut orbit: ResMut<OrbitCamera>,
+) {
+    let (entity, mut projection) = camera.into_inner();
+    // the orbit camera takes over from an axis view
+    if orbit.active && views.view.is_some() {
+        views.view = None;
+        *projection = Projection::default();
+        return;
+    }
+
+    if keyboard_input.just_pressed(CONTROLS_VIEW_PERSPECTIVE) && views.view.is_some() {
+        views.view = None;
+        *projection = Projection::default();
+        commands.entity(entity).insert(FlyCam);
+        return;
+    }
+
+    let opposite = keyboard_input.any_pressed(CONTROLS_VIEW_OPPOSITE_MODIFIERS);
+    let view = [
+        (CONTROLS_VIEW_FRONT, 2, true),
+        (CONTROLS_VIEW_RIGHT, 0, false),
+        (CONTROLS_VIEW_TOP, 1, false),
+    ]
+    .into_iter()
+    .find(|(key, ..)| keyboard_input.just_pressed(*key))
+    .map(|(_, axis, positive)| AxisView {
+        axis,
+        positive: positive != opposite,
+    });
+    let Some(view) = view else {
+        return;
+    };
+
+    if views.view.is_none() {
+        let size = vlox_settings.data.size();
+        views.center = Vec3::ZERO;
+        views.height = size * VIEW_MARGIN;
+        views.plane = 0.0;
+        orbit.active = false;
+        commands.entity(entity).remove::<FlyCam>();
+    }
+    views.view = Some(view);
+    *projection = Projection::Orthographic(OrthographicProjection {
+        scaling_mode: ScalingMode::FixedVertical {
+            viewport_height: views.height,
+        },
+        ..OrthographicProjection::default_3d()
+    });
+}
+
+fn control_view(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mouse_motion: Res<AccumulatedMouseMotion>,
+    window: Single<&Window>,
+    camera: Single<(&mut Transform, &mut Projection), With<Camera>>,
+    vlox_settings: Res<VloxSettings>,
+    mut views: ResMut<AxisViews>,
+) {
+    let Some(view) = views.view else {
+        return;
+    };
+    let (mut transform, mut projection) = camera.into_inner();
+
+    if keyboard_input.just_pressed(CONTROLS_VIEW_ZOOM_IN) {
+        views.height *= ZOOM_STEP;
+    }
+    if keyboard_input.just_pressed(CONTROLS_VIEW_ZOOM_OUT) {
+        views.height /= ZOOM_STEP;
+    }
+    if let Projection::Orthographic(ortho) = &mut *projection {
+        ortho.scaling_mode = ScalingMode::FixedVertical {
+            viewport_height: views.height,
+        };
+    }
+
+    // panning needs the cursor to be grabbed, like looking around in the other cameras
+    if window.cursor_options.grab_mode != CursorGrabMode::None {
+        let motion = mouse_motion.delta * views.height * PAN_SENSITIVITY;
+        let pan = transform.rotation * Vec3::new(-motion.x, motion.y, 0.0);
+        views.center += pan;
+    }
+
+    let data = &vlox_settings.data;
+    let step = data.vlox_size(data.num_vlox(vlox_settings.selected_depth));
+    let nearer = keyboard_input.just_pressed(CONTROLS_PLANE_NEARER) as i32
+        - keyboard_input.just_pressed(CONTROLS_PLANE_FURTHER) as i32;
+    if nearer != 0 {
+        // snap to the selected depth's grid, so placed vloxes line up with it
+        views.move_plane(view, nearer, step, data.size());
+    }
+
+    *transform = views.transform(view, data.size());
+}
+
+/// Draws the working plane's grid at the selected depth's vlox size
+fn draw_grid(mut gizmos: Gizmos, vlox_settings: Res<VloxSettings>, views: Res<AxisViews>) {
+    let Some(view) = views.view else {
+        return;
+    };
+    let data = &vlox_settings.data;
+    let num_vlox = data.num_vlox(vlox_settings.selected_depth);
+    let step = data.vlox_size(num_vlox);
+    let (normal, rotation) = match view.axis {
+        0 => (Vec3::X, Quat::from_rotation_y(std::f32::consts::FRAC_PI_2)),
+        1 => (Vec3::Y, Quat::from_rotation_x(std::f32::consts::FRAC_PI_2)),
+        _ => (Vec3::Z, Quat::IDENTITY),
+    };
+    gizmos.grid(
+        Isometry3d::new(normal * views.plane, rotation),
+        UVec2::splat(num_vlox as u32),
+        Vec2::splat(step),
+        GRID_COLOR,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOP: AxisView = AxisView {
+        axis: 1,
+        positive: false,
+    };
+
+    #[test]
+    fn places_on_the_camera_side_of_the_working_plane() {
+        // four vloxes a side at depth 2, from -2 to 2
+        let data = VloxData::new(2);
+        let mut views = AxisViews {
+            view: Some(TOP),
+            ..default()
+        };
+        let origin = Vec3::new(0.3, 10.0, -0.7);
+        assert_eq!(
+            Some((Some((2, 2, 1)), 10.0)),
+            views.place_on_plane(&data, origin, Vec3::NEG_Y, 2)
+        );
+        // deep down it is still the vlox on the camera's side, where half a vlox is lost to
+        // rounding
+        let below = AxisViews {
+            plane: 1.0,
+            ..views
+        };
+        let deep = below.place_on_plane(&data, Vec3::new(0.3, -10.0, -0.7), Vec3::Y, 26);
+        assert_eq!(
+            Some((3 << 24) - 1),
+            deep.and_then(|(cell, _)| cell).map(|cell| cell.1)
+        );
+        // looking away from the plane, or in the perspective view, there is nothing to place on
+        assert_eq!(None, views.place_on_plane(&data, origin, Vec3::Y, 2));
+        views.view = None;
+        assert_eq!(None, views.place_on_plane(&data, origin, Vec3::NEG_Y, 2));
+    }
+
+    #[test]
+    fn working_plane_snaps_to_the_grid_and_stays_inside() {
+        let data = VloxData::new(2);
+        let mut views = AxisViews {
+            view: Some(TOP),
+            plane: 0.3,
+            ..default()
+        };
+        let origin = Vec3::new(0.3, 10.0, -0.7);
+        let place = |views: &AxisViews| {
+            views
+                .place_on_plane(&data, origin, Vec3::NEG_Y, 2)
+                .and_then(|(cell, _)| cell)
+        };
+
+        views.move_plane(TOP, 1, 1.0, data.size());
+        assert_eq!(1.0, views.plane);
+        assert_eq!(Some((2, 3, 1)), place(&views));
+        // the topmost vloxes are the last that can be placed against it
+        views.move_plane(TOP, 5, 1.0, data.size());
+        assert_eq!(1.0, views.plane);
+
+        views.move_plane(TOP, -8, 1.0, data.size());
+        assert_eq!(-2.0, views.plane);
+        assert_eq!(Some((2, 0, 1)), place(&views));
+
+        // at half the size the grid is finer
+        views.move_plane(TOP, 1, 0.5, data.size());
+        assert_eq!(-1.5, views.plane);
+        assert_eq!(
+            Some((4, 1, 2)),
+            views
+                .place_on_plane(&data, origin, Vec3::NEG_Y, 3)
+                .and_then(|(cell, _)| cell)
+        );
+
+        // looking up from below, the plane stops a vlox short of the bottom instead
+        let bottom = AxisView {
+            axis: 1,
+            positive: true,
+        };
+        views.move_plane(bottom, 8, 1.0, data.size());
+        assert_eq!(-1.0, views.plane);
+    }
+}
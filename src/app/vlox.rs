@@ -1,6 +1,35 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+pub use clip::ClipPlane;
+pub use csg::{CsgOp, MaterialRule};
+pub use dag::{DagStats, VloxDag};
+pub use edit::{Leaf, MAX_FLOOD_FILL};
+pub use light::LightMap;
+pub use palette::PaletteFormat;
+pub use patch::VloxPatch;
+pub use prune::PruneRule;
+pub use region::{Clipboard, Orientation};
+pub use summary::VloxSummary;
+
+mod clip;
+mod csg;
+mod dag;
+mod edit;
+mod light;
+mod palette;
+mod patch;
+mod prune;
+mod raycast;
+mod region;
+mod subdivide;
+mod summary;
 
 pub type MaterialId = u16;
+/// The material id every `MaterialMap` keeps for empty space
+pub const VOID: MaterialId = 0;
 
 #[derive(Default)]
 pub struct MaterialMap {
@@ -8,11 +37,15 @@ pub struct MaterialMap {
 }
 impl MaterialMap {
     pub fn color(&self, id: MaterialId, vx: u128, vy: u128, vz: u128, depth: u8) -> VloxColor {
-        match &self.map[&id] {
+        // ids missing from the map (e.g. after loading a smaller palette) aren't drawn
+        match self.map.get(&id).unwrap_or(&Material::Void) {
             Material::Void => VloxColor::Void,
             Material::Solid(builder) => {
                 let color_index = builder.data.get(vx, vy, vz, depth);
-                VloxColor::Solid(builder.colors[color_index as usize])
+                match builder.colors.get(color_index as usize) {
+                    Some(color) => VloxColor::Solid(*color),
+                    None => VloxColor::Void,
+                }
             }
             Material::Custom(builder) => {
                 //TODO: call wasm to get real materialid, then lookup color
@@ -23,6 +56,31 @@ impl MaterialMap {
     pub fn get(&self, id: MaterialId) -> Option<&Material> {
         self.map.get(&id)
     }
+    /// Ids of every material that isn't void, in ascending order
+    pub fn ids(&self) -> Vec<MaterialId> {
+        let mut ids: Vec<MaterialId> = self
+            .map
+            .iter()
+            .filter(|(_, material)| !matches!(material, Material::Void))
+            .map(|(id, _)| *id)
+            .collect();
+        ids.sort();
+        ids
+    }
+    pub fn name(&self, id: MaterialId) -> Option<&str> {
+        match self.map.get(&id)? {
+            Material::Void => None,
+            Material::Solid(material) => Some(&material.name),
+            Material::Custom(material) => Some(&material.name),
+        }
+    }
+    /// Light output of a material, 0.0 for anything that doesn't glow.
+    pub fn emissive(&self, id: MaterialId) -> f32 {
+        match self.map.get(&id) {
+            Some(Material::Solid(material)) => material.emissive,
+            _ => 0.0,
+        }
+    }
     pub fn set(&mut self, id: MaterialId, material: Material) {
         self.map.insert(id, material);
     }
@@ -43,12 +101,17 @@ pub struct SolidMaterial {
     pub name: String,
     pub data: VloxData,
     pub colors: Vec<Color>,
+    /// Light output per vlox, 0.0 means the material doesn't emit light
+    pub emissive: f32,
+    pub metallic: f32,
+    pub roughness: f32,
 }
 pub struct CustomMaterial {
     pub name: String,
     pub wasm: Vec<u8>,
 }
 
+/// Linear RGBA
 #[derive(Copy, Clone, PartialEq)]
 pub struct Color {
     r: f32,
@@ -65,6 +128,29 @@ impl Color {
     }
 }
 
+/// A group of face-adjacent emissive vloxes, lit by a single light
+pub struct EmissiveCluster {
+    pub center: (f32, f32, f32),
+    pub color: Color,
+    /// Summed emissive strength of every vlox in the cluster
+    pub strength: f32,
+}
+
+/// The faces of a vlox: the offset to the vlox they face and their corners in winding order
+const FACES: [([i128; 3], [[u128; 3]; 4]); 6] = [
+    ([-1, 0, 0], [[0, 0, 0], [0, 0, 1], [0, 1, 1], [0, 1, 0]]),
+    ([1, 0, 0], [[1, 0, 0], [1, 1, 0], [1, 1, 1], [1, 0, 1]]),
+    ([0, -1, 0], [[0, 0, 0], [1, 0, 0], [1, 0, 1], [0, 0, 1]]),
+    ([0, 1, 0], [[0, 1, 0], [0, 1, 1], [1, 1, 1], [1, 1, 0]]),
+    ([0, 0, -1], [[0, 0, 0], [0, 1, 0], [1, 1, 0], [1, 0, 0]]),
+    ([0, 0, 1], [[0, 0, 1], [1, 0, 1], [1, 1, 1], [0, 1, 1]]),
+];
+
+fn in_bounds((x, y, z): (i128, i128, i128), blocks: u128) -> bool {
+    let blocks = blocks as i128;
+    x >= 0 && y >= 0 && z >= 0 && x < blocks && y < blocks && z < blocks
+}
+
 #[repr(u8)]
 #[derive(Copy, Clone)]
 pub enum SubVlox {
@@ -78,7 +164,7 @@ pub enum SubVlox {
     X1Y1Z1 = 7,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct VloxData {
     size: f32,
     root: Vlox,
@@ -130,6 +216,62 @@ impl VloxData {
     pub fn set(&mut self, x: u128, y: u128, z: u128, depth: u8, value: MaterialId) {
         self.root.set(self.xyz_to_path(x, y, z, depth), value);
     }
+    /// The face-connected vloxes at `depth` with the same material as the start vlox,
+    /// or None if there are more than `max_vloxes` of them
+    pub fn flood_region(
+        &self,
+        x: u128,
+        y: u128,
+        z: u128,
+        depth: u8,
+        max_vloxes: usize,
+    ) -> Option<Vec<(u128, u128, u128)>> {
+        let blocks = self.num_vlox(depth);
+        let value = self.get(x, y, z, depth);
+
+        let mut region = vec![(x, y, z)];
+        let mut seen = HashSet::from([(x, y, z)]);
+        let mut next = 0;
+        while let Some(&(x, y, z)) = region.get(next) {
+            next += 1;
+            for (dx, dy, dz) in [
+                (-1, 0, 0),
+                (1, 0, 0),
+                (0, -1, 0),
+                (0, 1, 0),
+                (0, 0, -1),
+                (0, 0, 1),
+            ] {
+                let neighbour = (x as i128 + dx, y as i128 + dy, z as i128 + dz);
+                if !in_bounds(neighbour, blocks) {
+                    continue;
+                }
+                let neighbour = (
+                    neighbour.0 as u128,
+                    neighbour.1 as u128,
+                    neighbour.2 as u128,
+                );
+                if self.get(neighbour.0, neighbour.1, neighbour.2, depth) == value
+                    && seen.insert(neighbour)
+                {
+                    if region.len() == max_vloxes {
+                        return None;
+                    }
+                    region.push(neighbour);
+                }
+            }
+        }
+        Some(region)
+    }
+    /// Material and stored depth of the leaf containing the vlox at `depth`. Vloxes subdivided
+    /// further than `depth` are returned as they are, at `depth`.
+    pub fn leaf(&self, x: u128, y: u128, z: u128, depth: u8) -> (MaterialId, u8) {
+        self.root.leaf(self.xyz_to_path(x, y, z, depth), 0)
+    }
+    /// Number of `Vlox` nodes stored, including the root
+    pub fn node_count(&self) -> usize {
+        self.root.node_count()
+    }
 
     //max depth: 128. Anything more won't be representable as u128.
     fn xyz_to_path(&self, mut x: u128, mut y: u128, mut z: u128, depth: u8) -> Vec<SubVlox> {
@@ -173,10 +315,76 @@ impl VloxData {
         }
         path
     }
+    /// Groups emissive vloxes at the given depth into clusters of face-adjacent vloxes
+    pub fn emissive_clusters(&self, depth: u8, materials: &MaterialMap) -> Vec<EmissiveCluster> {
+        let blocks = 2_u128.pow(depth as u32);
+
+        let mut emissive = HashSet::new();
+        for vx in 0..blocks {
+            for vy in 0..blocks {
+                for vz in 0..blocks {
+                    if materials.emissive(self.get(vx, vy, vz, depth)) > 0.0 {
+                        emissive.insert((vx, vy, vz));
+                    }
+                }
+            }
+        }
+
+        let mut clusters = vec![];
+        while let Some(&start) = emissive.iter().next() {
+            emissive.remove(&start);
+            let mut stack = vec![start];
+            let mut center = (0.0, 0.0, 0.0);
+            let mut color = [0.0; 4];
+            let mut strength = 0.0;
+            let mut vloxes = 0.0;
+            while let Some((vx, vy, vz)) = stack.pop() {
+                let id = self.get(vx, vy, vz, depth);
+                let (x, y, z) = self.vlox_xyz_to_xyz_f32(vx, vy, vz, depth);
+                center.0 += x;
+                center.1 += y;
+                center.2 += z;
+                if let VloxColor::Solid(c) = materials.color(id, 0, 0, 0, 0) {
+                    for (sum, channel) in color.iter_mut().zip(c.as_f32x4()) {
+                        *sum += channel;
+                    }
+                }
+                strength += materials.emissive(id);
+                vloxes += 1.0;
+
+                let neighbours = [
+                    (vx.wrapping_sub(1), vy, vz),
+                    (vx + 1, vy, vz),
+                    (vx, vy.wrapping_sub(1), vz),
+                    (vx, vy + 1, vz),
+                    (vx, vy, vz.wrapping_sub(1)),
+                    (vx, vy, vz + 1),
+                ];
+                for neighbour in neighbours {
+                    if emissive.remove(&neighbour) {
+                        stack.push(neighbour);
+                    }
+                }
+            }
+            clusters.push(EmissiveCluster {
+                center: (center.0 / vloxes, center.1 / vloxes, center.2 / vloxes),
+                color: Color::new(
+                    color[0] / vloxes,
+                    color[1] / vloxes,
+                    color[2] / vloxes,
+                    color[3] / vloxes,
+                ),
+                strength,
+            });
+        }
+        clusters
+    }
     pub fn compute_mesh_at_depth(
         &self,
         depth: u8,
         materials: &MaterialMap,
+        light: Option<&LightMap>,
+        clip: Option<&ClipPlane>,
     ) -> (Vec<[f32; 3]>, Vec<[f32; 3]>, Vec<[f32; 4]>, Vec<u32>) {
         let mut vertices = vec![];
         let mut normals = vec![];
@@ -189,86 +397,68 @@ impl VloxData {
         }
 
         let blocks = 2_u128.pow(depth as u32);
+        // light is only usable if it was computed for the same cells
+        let light = light.filter(|light| light.depth() == depth);
+        let offset = self.size / 2.0;
+        let hidden = |x: i128, y: i128, z: i128| {
+            clip.is_some_and(|clip| {
+                clip.hides([
+                    size * x as f32 - offset,
+                    size * y as f32 - offset,
+                    size * z as f32 - offset,
+                ])
+            })
+        };
 
         //iterate potential vertices
-        let mut x;
-        let mut y;
-        let mut z;
         let mut id;
-        let mut adjacent_id;
-        let mut adjacent_color;
         for vx in 0..blocks {
             for vy in 0..blocks {
                 for vz in 0..blocks {
+                    if hidden(vx as i128, vy as i128, vz as i128) {
+                        continue;
+                    }
                     id = self.get(vx, vy, vz, depth);
                     if let VloxColor::Solid(color) = materials.color(id, 0, 0, 0, 0) {
-                        x = vx as f32;
-                        y = vy as f32;
-                        z = vz as f32;
-
-                        //right
-                        adjacent_id = self.get(vx - 1, vy, vz, depth);
-                        adjacent_color = materials.color(adjacent_id, 0, 0, 0, 0);
-                        if vx == 0 || adjacent_color == VloxColor::Void {
-                            vertices.push([size * x, size * y, size * z]);
-                            vertices.push([size * x, size * y, size * (z + 1.0)]);
-                            vertices.push([size * x, size * (y + 1.0), size * (z + 1.0)]);
-                            vertices.push([size * x, size * (y + 1.0), size * z]);
-                            normals.push([-1.0, 0.0, 0.0]);
-                            normals.push([-1.0, 0.0, 0.0]);
-                            normals.push([-1.0, 0.0, 0.0]);
-                            normals.push([-1.0, 0.0, 0.0]);
-                            colors.push(color.as_f32x4());
-                            colors.push(color.as_f32x4());
-                            colors.push(color.as_f32x4());
-                            colors.push(color.as_f32x4());
-                            indices.push(vertices.len() as u32 - 4);
-                            indices.push(vertices.len() as u32 - 3);
-                            indices.push(vertices.len() as u32 - 2);
-                            indices.push(vertices.len() as u32 - 2);
-                            indices.push(vertices.len() as u32 - 1);
-                            indices.push(vertices.len() as u32 - 4);
-                        }
-                        //left
-                        adjacent_id = self.get(vx + 1, vy, vz, depth);
-                        adjacent_color = materials.color(adjacent_id, 0, 0, 0, 0);
-                        if vx == blocks - 1 || adjacent_color == VloxColor::Void {
-                            vertices.push([size * (x + 1.0), size * y, size * z]);
-                            vertices.push([size * (x + 1.0), size * y, size * (z + 1.0)]);
-                            vertices.push([size * (x + 1.0), size * (y + 1.0), size * (z + 1.0)]);
-                            vertices.push([size * (x + 1.0), size * (y + 1.0), size * z]);
-                            normals.push([1.0, 0.0, 0.0]);
-                            normals.push([1.0, 0.0, 0.0]);
-                            normals.push([1.0, 0.0, 0.0]);
-                            normals.push([1.0, 0.0, 0.0]);
-                            colors.push(color.as_f32x4());
-                            colors.push(color.as_f32x4());
-                            colors.push(color.as_f32x4());
-                            colors.push(color.as_f32x4());
-                            indices.push(vertices.len() as u32 - 4);
-                            indices.push(vertices.len() as u32 - 1);
-                            indices.push(vertices.len() as u32 - 2);
-                            indices.push(vertices.len() as u32 - 2);
-                            indices.push(vertices.len() as u32 - 3);
-                            indices.push(vertices.len() as u32 - 4);
-                        }
+                        for (normal, corners) in FACES {
+                            // faces on the edge of the data, or next to a void or clipped vlox,
+                            // are visible
+                            let adjacent = (
+                                vx as i128 + normal[0],
+                                vy as i128 + normal[1],
+                                vz as i128 + normal[2],
+                            );
+                            let visible = !in_bounds(adjacent, blocks)
+                                || hidden(adjacent.0, adjacent.1, adjacent.2)
+                                || materials.color(
+                                    self.get(
+                                        adjacent.0 as u128,
+                                        adjacent.1 as u128,
+                                        adjacent.2 as u128,
+                                        depth,
+                                    ),
+                                    0,
+                                    0,
+                                    0,
+                                    0,
+                                ) == VloxColor::Void;
+                            if !visible {
+                                continue;
+                            }
 
-                        //bottom
-                        adjacent_id = self.get(vx, vy - 1, vz, depth);
-                        adjacent_color = materials.color(adjacent_id, 0, 0, 0, 0);
-                        if vy == 0 || adjacent_color == VloxColor::Void {
-                            vertices.push([size * x, size * y, size * z]);
-                            vertices.push([size * (x + 1.0), size * y, size * z]);
-                            vertices.push([size * (x + 1.0), size * y, size * (z + 1.0)]);
-                            vertices.push([size * x, size * y, size * (z + 1.0)]);
-                            normals.push([0.0, -1.0, 0.0]);
-                            normals.push([0.0, -1.0, 0.0]);
-                            normals.push([0.0, -1.0, 0.0]);
-                            normals.push([0.0, -1.0, 0.0]);
-                            colors.push(color.as_f32x4());
-                            colors.push(color.as_f32x4());
-                            colors.push(color.as_f32x4());
-                            colors.push(color.as_f32x4());
+                            for corner in corners {
+                                let (x, y, z) = (vx + corner[0], vy + corner[1], vz + corner[2]);
+                                vertices.push([size * x as f32, size * y as f32, size * z as f32]);
+                                normals.push([
+                                    normal[0] as f32,
+                                    normal[1] as f32,
+                                    normal[2] as f32,
+                                ]);
+                                colors.push(match light {
+                                    Some(light) => light.shade(color, (x, y, z), normal),
+                                    None => color.as_f32x4(),
+                                });
+                            }
                             indices.push(vertices.len() as u32 - 4);
                             indices.push(vertices.len() as u32 - 3);
                             indices.push(vertices.len() as u32 - 2);
@@ -276,82 +466,11 @@ impl VloxData {
                             indices.push(vertices.len() as u32 - 1);
                             indices.push(vertices.len() as u32 - 4);
                         }
-                        //top
-                        adjacent_id = self.get(vx, vy + 1, vz, depth);
-                        adjacent_color = materials.color(adjacent_id, 0, 0, 0, 0);
-                        if vy == blocks - 1 || adjacent_color == VloxColor::Void {
-                            vertices.push([size * x, size * (y + 1.0), size * z]);
-                            vertices.push([size * (x + 1.0), size * (y + 1.0), size * z]);
-                            vertices.push([size * (x + 1.0), size * (y + 1.0), size * (z + 1.0)]);
-                            vertices.push([size * x, size * (y + 1.0), size * (z + 1.0)]);
-                            normals.push([0.0, 1.0, 0.0]);
-                            normals.push([0.0, 1.0, 0.0]);
-                            normals.push([0.0, 1.0, 0.0]);
-                            normals.push([0.0, 1.0, 0.0]);
-                            colors.push(color.as_f32x4());
-                            colors.push(color.as_f32x4());
-                            colors.push(color.as_f32x4());
-                            colors.push(color.as_f32x4());
-                            indices.push(vertices.len() as u32 - 4);
-                            indices.push(vertices.len() as u32 - 1);
-                            indices.push(vertices.len() as u32 - 2);
-                            indices.push(vertices.len() as u32 - 2);
-                            indices.push(vertices.len() as u32 - 3);
-                            indices.push(vertices.len() as u32 - 4);
-                        }
-
-                        //back
-                        adjacent_id = self.get(vx, vy, vz - 1, depth);
-                        adjacent_color = materials.color(adjacent_id, 0, 0, 0, 0);
-                        if vz == 0 || adjacent_color == VloxColor::Void {
-                            vertices.push([size * x, size * y, size * z]);
-                            vertices.push([size * x, size * (y + 1.0), size * z]);
-                            vertices.push([size * (x + 1.0), size * (y + 1.0), size * z]);
-                            vertices.push([size * (x + 1.0), size * y, size * z]);
-                            normals.push([0.0, 0.0, -1.0]);
-                            normals.push([0.0, 0.0, -1.0]);
-                            normals.push([0.0, 0.0, -1.0]);
-                            normals.push([0.0, 0.0, -1.0]);
-                            colors.push(color.as_f32x4());
-                            colors.push(color.as_f32x4());
-                            colors.push(color.as_f32x4());
-                            colors.push(color.as_f32x4());
-                            indices.push(vertices.len() as u32 - 4);
-                            indices.push(vertices.len() as u32 - 3);
-                            indices.push(vertices.len() as u32 - 2);
-                            indices.push(vertices.len() as u32 - 2);
-                            indices.push(vertices.len() as u32 - 1);
-                            indices.push(vertices.len() as u32 - 4);
-                        }
-                        //front
-                        adjacent_id = self.get(vx, vy, vz + 1, depth);
-                        adjacent_color = materials.color(adjacent_id, 0, 0, 0, 0);
-                        if vz == blocks - 1 || adjacent_color == VloxColor::Void {
-                            vertices.push([size * x, size * y, size * (z + 1.0)]);
-                            vertices.push([size * x, size * (y + 1.0), size * (z + 1.0)]);
-                            vertices.push([size * (x + 1.0), size * (y + 1.0), size * (z + 1.0)]);
-                            vertices.push([size * (x + 1.0), size * y, size * (z + 1.0)]);
-                            normals.push([0.0, 0.0, 1.0]);
-                            normals.push([0.0, 0.0, 1.0]);
-                            normals.push([0.0, 0.0, 1.0]);
-                            normals.push([0.0, 0.0, 1.0]);
-                            colors.push(color.as_f32x4());
-                            colors.push(color.as_f32x4());
-                            colors.push(color.as_f32x4());
-                            colors.push(color.as_f32x4());
-                            indices.push(vertices.len() as u32 - 4);
-                            indices.push(vertices.len() as u32 - 1);
-                            indices.push(vertices.len() as u32 - 2);
-                            indices.push(vertices.len() as u32 - 2);
-                            indices.push(vertices.len() as u32 - 3);
-                            indices.push(vertices.len() as u32 - 4);
-                        }
                     }
                 }
             }
         }
 
-        let offset = self.size / 2.0;
         for i in 0..vertices.len() {
             vertices[i][0] -= offset;
             vertices[i][1] -= offset;
@@ -359,24 +478,58 @@ impl VloxData {
         }
         (vertices, normals, colors, indices)
     }
+    /// True if any part of the vlox at the given depth is void, even if it is subdivided further
+    pub fn contains_void(
+        &self,
+        x: u128,
+        y: u128,
+        z: u128,
+        depth: u8,
+        materials: &MaterialMap,
+    ) -> bool {
+        self.root.any_leaf(self.xyz_to_path(x, y, z, depth), &|id| {
+            materials.color(id, 0, 0, 0, 0) == VloxColor::Void
+        })
+    }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 struct Vlox {
+    /// Material of the vlox if it is a leaf, and of the children it doesn't store if not
     value: MaterialId,
-    children: Vec<Option<Vlox>>,
+    /// Shared with identical subtrees while the DAG is on, see `VloxDag`, so they are only
+    /// changed through `Arc::make_mut`, which copies them first if they are shared
+    children: Vec<Option<Arc<Vlox>>>,
+    summary: VloxSummary,
 }
 impl Vlox {
     fn new(value: MaterialId) -> Self {
         Self {
             value,
             children: vec![None; 8],
+            summary: VloxSummary::of(value),
+        }
+    }
+    /// A vlox with no children
+    fn uniform(value: MaterialId) -> Self {
+        Self {
+            value,
+            children: vec![],
+            summary: VloxSummary::of(value),
+        }
+    }
+    /// The material a subdivided vlox stands for when looked at whole
+    fn representative(&self) -> MaterialId {
+        if self.children.is_empty() {
+            self.value
+        } else {
+            self.summary.value()
         }
     }
     fn get(&self, path: Vec<SubVlox>) -> MaterialId {
         // if we reached the end of the path, return value
         if path.len() == 0 {
-            return self.value;
+            return self.representative();
         }
         if self.children.len() == 0 {
             return self.value;
@@ -393,6 +546,7 @@ impl Vlox {
         if path.len() == 0 {
             self.value = value;
             self.children = vec![];
+            self.summary = VloxSummary::of(value);
             return;
         }
         if self.children.len() == 0 {
@@ -400,13 +554,52 @@ impl Vlox {
         }
         // go to the next stage of the path, creating a new node if required
         if let Some(child) = &mut self.children[path[0] as usize] {
-            child.set(path[1..].to_vec(), value);
+            Arc::make_mut(child).set(path[1..].to_vec(), value);
         } else {
             let mut vlox = Vlox::new(self.value);
             vlox.set(path[1..].to_vec(), value);
-            self.children[path[0] as usize] = Some(vlox);
+            self.children[path[0] as usize] = Some(Arc::new(vlox));
+        }
+        self.summarise();
+    }
+    fn leaf(&self, path: Vec<SubVlox>, depth: u8) -> (MaterialId, u8) {
+        if path.is_empty() || self.children.is_empty() {
+            return (self.representative(), depth);
+        }
+        match &self.children[path[0] as usize] {
+            Some(child) => child.leaf(path[1..].to_vec(), depth + 1),
+            // a child that was never created is a leaf with this vlox's value
+            None => (self.value, depth + 1),
         }
     }
+    fn node_count(&self) -> usize {
+        1 + self
+            .children
+            .iter()
+            .flatten()
+            .map(|child| child.node_count())
+            .sum::<usize>()
+    }
+    fn any_leaf(&self, path: Vec<SubVlox>, f: &impl Fn(MaterialId) -> bool) -> bool {
+        if path.is_empty() {
+            return self.any_leaf_below(f);
+        }
+        match self.children.get(path[0] as usize) {
+            Some(Some(child)) => child.any_leaf(path[1..].to_vec(), f),
+            _ => f(self.value),
+        }
+    }
+    fn any_leaf_below(&self, f: &impl Fn(MaterialId) -> bool) -> bool {
+        // leaves, and children that were never created, have this vlox's value
+        let uncovered = self.children.is_empty() || self.children.iter().any(|c| c.is_none());
+        if uncovered && f(self.value) {
+            return true;
+        }
+        self.children
+            .iter()
+            .flatten()
+            .any(|child| child.any_leaf_below(f))
+    }
 }
 
 #[cfg(test)]
@@ -448,4 +641,65 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn emissive_clusters_merge_adjacent_vloxes() {
+        let mut materials = MaterialMap::default();
+        materials.set(0, Material::Void);
+        materials.set(
+            1,
+            Material::Solid(SolidMaterial {
+                name: "Lamp".to_string(),
+                data: VloxData::new(0),
+                colors: vec![Color::new(1.0, 1.0, 0.0, 1.0)],
+                emissive: 1.0,
+                metallic: 0.0,
+                roughness: 0.5,
+            }),
+        );
+
+        let mut data = VloxData::new(2);
+        data.set(0, 0, 0, 2, 1);
+        data.set(1, 0, 0, 2, 1);
+        data.set(3, 3, 3, 2, 1);
+
+        let mut clusters = data.emissive_clusters(2, &materials);
+        clusters.sort_by(|a, b| a.strength.total_cmp(&b.strength));
+        assert_eq!(2, clusters.len());
+        assert_eq!(1.0, clusters[0].strength);
+        assert_eq!((1.5, 1.5, 1.5), clusters[0].center);
+        assert_eq!(2.0, clusters[1].strength);
+        assert_eq!((-1.0, -1.5, -1.5), clusters[1].center);
+    }
+
+    #[test]
+    fn flood_region_stays_within_one_material() {
+        let mut data = VloxData::new(2);
+        // a wall splitting the bottom layer in two
+        for z in 0..4 {
+            data.set(1, 0, z, 2, 1);
+        }
+
+        let mut wall = data.flood_region(1, 0, 2, 2, 64).unwrap();
+        wall.sort();
+        assert_eq!(vec![(1, 0, 0), (1, 0, 1), (1, 0, 2), (1, 0, 3)], wall);
+
+        // the void goes around the wall through the layers above
+        assert_eq!(60, data.flood_region(0, 0, 0, 2, 64).unwrap().len());
+        assert_eq!(None, data.flood_region(0, 0, 0, 2, 59));
+    }
+
+    #[test]
+    fn leaf_reports_the_stored_depth() {
+        let mut data = VloxData::new(2);
+        data.set(1, 1, 1, 1, 1);
+        data.set(0, 0, 0, 3, 2);
+
+        assert_eq!((1, 1), data.leaf(7, 7, 7, 3));
+        assert_eq!((1, 1), data.leaf(1, 1, 1, 1));
+        assert_eq!((2, 3), data.leaf(0, 0, 0, 5));
+        // the void next to the small vlox was never created
+        assert_eq!((VOID, 3), data.leaf(1, 0, 0, 3));
+        assert_eq!((VOID, 1), data.leaf(0, 0, 0, 1));
+    }
 }
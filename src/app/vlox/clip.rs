@@ -0,0 +1,72 @@
+/// An axis aligned plane that hides everything on its positive side, for looking inside
+/// dense models. The mesher leaves hidden vloxes out and caps the cut, and raycasts pass
+/// through them.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ClipPlane {
+    /// 0, 1, 2 for x, y, z
+    pub axis: usize,
+    /// World position of the plane along the axis
+    pub position: f32,
+}
+impl ClipPlane {
+    /// True if a box with its lowest corner at `min` is entirely hidden
+    pub(super) fn hides(&self, min: [f32; 3]) -> bool {
+        min[self.axis] >= self.position
+    }
+    /// True if the plane goes through the box from `min` with sides of `size`
+    pub(super) fn cuts(&self, min: [f32; 3], size: f32) -> bool {
+        min[self.axis] < self.position && self.position < min[self.axis] + size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::raycast::Face;
+    use super::super::{Color, Material, MaterialMap, SolidMaterial, VloxData};
+    use super::*;
+
+    #[test]
+    fn clipped_vloxes_are_capped_and_passed_through() {
+        let mut materials = MaterialMap::default();
+        materials.set(0, Material::Void);
+        materials.set(
+            1,
+            Material::Solid(SolidMaterial {
+                name: "Stone".to_string(),
+                data: VloxData::new(0),
+                colors: vec![Color::new(1.0, 1.0, 1.0, 1.0)],
+                emissive: 0.0,
+                metallic: 0.0,
+                roughness: 0.5,
+            }),
+        );
+        let mut data = VloxData::new(2);
+        data.set(0, 0, 0, 0, 1);
+        let half = data.size() * 0.5;
+        let clip = ClipPlane {
+            axis: 0,
+            position: 0.0,
+        };
+
+        let (vertices, ..) = data.compute_mesh_at_depth(1, &materials, None, None);
+        assert_eq!(24 * 4, vertices.len());
+        // the half left is a 1x2x2 block of cells, with its cut side capped
+        let (vertices, ..) = data.compute_mesh_at_depth(1, &materials, None, Some(&clip));
+        assert_eq!(16 * 4, vertices.len());
+        assert!(vertices.iter().all(|vertex| vertex[0] <= 0.0));
+
+        let hit = data
+            .raycast((5.0, 0.5, 0.5), (-1.0, 0.0, 0.0), 8, None)
+            .unwrap();
+        assert_eq!(5.0 - half, hit.distance);
+        let hit = data
+            .raycast((5.0, 0.5, 0.5), (-1.0, 0.0, 0.0), 8, Some(&clip))
+            .unwrap();
+        assert_eq!(5.0, hit.distance);
+        assert_eq!(Face::PosX, hit.face);
+        assert_eq!(
+            None,
+            data.raycast((1.0, 5.0, 0.5), (0.0, -1.0, 0.0), 8, Some(&clip))
+        );
+    }
+}
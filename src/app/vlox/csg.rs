@@ -0,0 +1,213 @@
+use std::sync::Arc;
+
+use super::{MaterialId, Vlox, VloxData, VOID};
+
+/// How two trees are combined, treating void as empty and every other material as solid
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CsgOp {
+    Union,
+    Subtract,
+    Intersect,
+    Xor,
+}
+impl CsgOp {
+    fn value(self, a: MaterialId, b: MaterialId, rule: MaterialRule) -> MaterialId {
+        match (self, a != VOID, b != VOID) {
+            (CsgOp::Union | CsgOp::Intersect, true, true) => rule.pick(a, b),
+            (CsgOp::Union | CsgOp::Subtract | CsgOp::Xor, true, false) => a,
+            (CsgOp::Union | CsgOp::Xor, false, true) => b,
+            _ => VOID,
+        }
+    }
+}
+
+/// Which material a vlox gets when it is solid in both trees
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum MaterialRule {
+    /// The tree being changed keeps its material
+    #[default]
+    Keep,
+    /// The other tree's material wins
+    Replace,
+}
+impl MaterialRule {
+    fn pick(self, a: MaterialId, b: MaterialId) -> MaterialId {
+        match self {
+            MaterialRule::Keep => a,
+            MaterialRule::Replace => b,
+        }
+    }
+}
+
+/// One side of a combination: a subtree, or a region with a single material
+#[derive(Copy, Clone)]
+enum Side<'a> {
+    Tree(&'a Vlox),
+    Uniform(MaterialId),
+}
+impl<'a> Side<'a> {
+    fn of(vlox: &'a Vlox) -> Self {
+        if vlox.children.is_empty() {
+            Side::Uniform(vlox.value)
+        } else {
+            Side::Tree(vlox)
+        }
+    }
+    fn child(self, i: usize) -> Self {
+        match self {
+            Side::Tree(vlox) => match &vlox.children[i] {
+                Some(child) => Side::of(child),
+                None => Side::Uniform(vlox.value),
+            },
+            uniform => uniform,
+        }
+    }
+}
+
+impl VloxData {
+    /// Adds `other`, with its root placed at the vlox at `depth`. `rule` picks the material
+    /// where both are solid.
+    pub fn union(
+        &mut self,
+        other: &VloxData,
+        at: (u128, u128, u128),
+        depth: u8,
+        rule: MaterialRule,
+    ) {
+        self.combine(other, at, depth, CsgOp::Union, rule);
+    }
+    /// Carves `other` out, with its root placed at the vlox at `depth`
+    pub fn subtract(&mut self, other: &VloxData, at: (u128, u128, u128), depth: u8) {
+        self.combine(other, at, depth, CsgOp::Subtract, MaterialRule::Keep);
+    }
+    /// Keeps only what overlaps `other`, with its root placed at the vlox at `depth`.
+    /// Everything outside that vlox is cleared. `rule` picks the material that is kept.
+    pub fn intersect(
+        &mut self,
+        other: &VloxData,
+        at: (u128, u128, u128),
+        depth: u8,
+        rule: MaterialRule,
+    ) {
+        self.combine(other, at, depth, CsgOp::Intersect, rule);
+    }
+    /// Keeps what is solid in exactly one of the two, with the root of `other` placed at
+    /// the vlox at `depth`
+    pub fn xor(&mut self, other: &VloxData, at: (u128, u128, u128), depth: u8) {
+        self.combine(other, at, depth, CsgOp::Xor, MaterialRule::Keep);
+    }
+
+    /// Combines subtree by subtree, so regions where either tree has no finer detail are
+    /// resolved at once instead of vlox by vlox
+    fn combine(
+        &mut self,
+        other: &VloxData,
+        (x, y, z): (u128, u128, u128),
+        depth: u8,
+        op: CsgOp,
+        rule: MaterialRule,
+    ) {
+        let path = self.xyz_to_path(x, y, z, depth);
+        let combined = combine(
+            Side::of(&self.root.subtree(&path)),
+            Side::of(&other.root),
+            op,
+            rule,
+        );
+        if op == CsgOp::Intersect {
+            self.root = Vlox::new(VOID);
+        }
+        self.root.set_subtree(&path, combined);
+        self.compact();
+    }
+}
+
+impl Vlox {
+    fn map(&self, f: &impl Fn(MaterialId) -> MaterialId) -> Vlox {
+        let mut vlox = Vlox::new(f(self.value));
+        vlox.children = self
+            .children
+            .iter()
+            .map(|child| child.as_ref().map(|child| Arc::new(child.map(f))))
+            .collect();
+        vlox.summarise();
+        vlox
+    }
+}
+
+fn combine(a: Side, b: Side, op: CsgOp, rule: MaterialRule) -> Vlox {
+    let mut vlox = match (a, b) {
+        (Side::Uniform(a), Side::Uniform(b)) => Vlox::uniform(op.value(a, b, rule)),
+        (Side::Tree(a), Side::Uniform(b)) => a.map(&|value| op.value(value, b, rule)),
+        (Side::Uniform(a), Side::Tree(b)) => b.map(&|value| op.value(a, value, rule)),
+        (Side::Tree(tree), Side::Tree(other)) => {
+            let mut vlox = Vlox::new(op.value(tree.value, other.value, rule));
+            vlox.children = (0..8)
+                .map(|i| Some(Arc::new(combine(a.child(i), b.child(i), op, rule))))
+                .collect();
+            vlox
+        }
+    };
+    vlox.compact();
+    vlox
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cutter() -> VloxData {
+        // an eighth of a cell, the lowest corner at depth 1
+        let mut cutter = VloxData::new(0);
+        cutter.set(0, 0, 0, 1, 3);
+        cutter
+    }
+
+    #[test]
+    fn union_and_subtract_at_an_offset() {
+        let mut wall = VloxData::new(2);
+        for x in 0..4 {
+            for y in 0..4 {
+                wall.set(x, y, 0, 2, 1);
+            }
+        }
+
+        let mut carved = VloxData::new(2);
+        carved.union(&wall, (0, 0, 0), 0, MaterialRule::Keep);
+        carved.subtract(&cutter(), (1, 1, 0), 2);
+        assert_eq!(VOID, carved.get(2, 2, 0, 3));
+        assert_eq!(1, carved.get(3, 2, 0, 3));
+        assert_eq!(1, carved.get(1, 1, 0, 2));
+        assert_eq!(1, carved.get(2, 2, 1, 3));
+
+        wall.union(&cutter(), (1, 1, 0), 2, MaterialRule::Keep);
+        assert_eq!(1, wall.get(2, 2, 0, 3));
+        wall.union(&cutter(), (1, 1, 0), 2, MaterialRule::Replace);
+        assert_eq!(3, wall.get(2, 2, 0, 3));
+        assert_eq!(VOID, wall.get(2, 2, 2, 3));
+    }
+
+    #[test]
+    fn intersect_and_xor() {
+        let mut block = VloxData::new(2);
+        block.set(0, 0, 0, 1, 1);
+        let nodes = block.node_count();
+
+        let mut both = VloxData::new(2);
+        both.set(0, 0, 0, 1, 1);
+        both.set(1, 1, 1, 1, 1);
+        both.intersect(&cutter(), (0, 0, 0), 1, MaterialRule::Replace);
+        assert_eq!(3, both.get(0, 0, 0, 2));
+        assert_eq!(VOID, both.get(1, 1, 1, 2));
+        assert_eq!(VOID, both.get(3, 3, 3, 2));
+
+        block.xor(&cutter(), (0, 0, 0), 1);
+        assert_eq!(VOID, block.get(0, 0, 0, 2));
+        assert_eq!(1, block.get(1, 0, 0, 2));
+        // xor with the same shape again puts it back, compacted as it was
+        block.xor(&cutter(), (0, 0, 0), 1);
+        assert_eq!(3, block.get(0, 0, 0, 2));
+        block.replace_material(3, 1);
+        assert_eq!(nodes, block.node_count());
+    }
+}
@@ -0,0 +1,201 @@
+use std::{
+    collections::HashMap,
+    mem,
+    sync::{Arc, Weak},
+};
+
+use super::{MaterialId, Vlox, VloxData};
+
+/// Stores identical subtrees of a `VloxData` once and shares them wherever they are used,
+/// turning the tree into a DAG, so repetitive builds like tiled floors take much less memory.
+///
+/// Edits copy the shared nodes they change before changing them, see `Vlox::children`, so an
+/// edit never shows up wherever else they are used. `VloxData::share` then shares the copies
+/// with identical nodes, only visiting the nodes that aren't shared yet.
+///
+/// Nodes are only held weakly, so ones no longer used are freed. A live node keeps its
+/// children alive, so their addresses can't have been reused while it can be found.
+#[derive(Debug, Default)]
+pub struct VloxDag {
+    /// Children are already shared, so a node is identified by its value and the addresses of
+    /// its children
+    nodes: HashMap<(MaterialId, Vec<Option<usize>>), Weak<Vlox>>,
+    /// Entries left after dead ones were last dropped
+    live: usize,
+}
+
+/// How much sharing subtrees saves over storing every use of them
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct DagStats {
+    /// Nodes in the tree, counting shared nodes once per use
+    pub tree_nodes: usize,
+    /// Nodes that are stored
+    pub unique_nodes: usize,
+    /// Bytes the tree would take without sharing
+    pub tree_bytes: usize,
+    /// Bytes it takes
+    pub dag_bytes: usize,
+}
+impl DagStats {
+    pub fn saved_bytes(&self) -> usize {
+        self.tree_bytes.saturating_sub(self.dag_bytes)
+    }
+}
+
+/// Dead entries are only dropped once there are more than twice this many
+const MIN_DAG_CLEANUP: usize = 1024;
+
+impl VloxDag {
+    fn key(vlox: &Vlox) -> (MaterialId, Vec<Option<usize>>) {
+        let children = vlox
+            .children
+            .iter()
+            .map(|child| child.as_ref().map(|child| Arc::as_ptr(child) as usize))
+            .collect();
+        (vlox.value, children)
+    }
+    /// Swaps `node` for the shared node identical to it, sharing it first if there isn't one
+    fn share(&mut self, node: &mut Arc<Vlox>) {
+        // shared nodes are found straight away, without visiting their children
+        if let Some(shared) = self.nodes.get(&Self::key(node)).and_then(Weak::upgrade) {
+            *node = shared;
+            return;
+        }
+        let mut vlox = Vlox::clone(node);
+        for child in vlox.children.iter_mut().flatten() {
+            self.share(child);
+        }
+        let key = Self::key(&vlox);
+        *node = match self.nodes.get(&key).and_then(Weak::upgrade) {
+            Some(shared) => shared,
+            None => {
+                let shared = Arc::new(vlox);
+                self.nodes.insert(key, Arc::downgrade(&shared));
+                shared
+            }
+        };
+        // edits leave dead entries behind, drop them once they could be half the map
+        if self.nodes.len() > 2 * self.live.max(MIN_DAG_CLEANUP) {
+            self.nodes.retain(|_, node| node.strong_count() > 0);
+            self.live = self.nodes.len();
+        }
+    }
+}
+
+impl VloxData {
+    /// Shares every subtree with the identical ones in `dag`. Subtrees that are already shared
+    /// are skipped, so after edits it only visits the nodes they copied or added.
+    pub fn share(&mut self, dag: &mut VloxDag) {
+        for child in self.root.children.iter_mut().flatten() {
+            dag.share(child);
+        }
+    }
+
+    /// Visits each stored node once, so it is as quick as the DAG is small
+    pub fn dag_stats(&self) -> DagStats {
+        let mut counted = HashMap::new();
+        let (tree_nodes, tree_bytes) = self.root.count(&mut counted);
+        DagStats {
+            tree_nodes,
+            // the root is stored in the data itself
+            unique_nodes: counted.len() + 1,
+            tree_bytes,
+            dag_bytes: counted.values().map(|&(_, _, bytes)| bytes).sum::<usize>()
+                + node_bytes(self.root.children.len()),
+        }
+    }
+}
+
+impl Vlox {
+    /// Nodes and bytes of the tree below and including this one. Each stored child's counts,
+    /// with the bytes it takes itself, are kept by address so shared nodes are counted once.
+    fn count(&self, counted: &mut HashMap<usize, (usize, usize, usize)>) -> (usize, usize) {
+        let mut totals = (1, node_bytes(self.children.len()));
+        for child in self.children.iter().flatten() {
+            let address = Arc::as_ptr(child) as usize;
+            let (nodes, bytes) = match counted.get(&address) {
+                Some(&(nodes, bytes, _)) => (nodes, bytes),
+                None => {
+                    let (nodes, bytes) = child.count(counted);
+                    counted.insert(address, (nodes, bytes, node_bytes(child.children.len())));
+                    (nodes, bytes)
+                }
+            };
+            totals = (totals.0 + nodes, totals.1 + bytes);
+        }
+        totals
+    }
+}
+
+/// Including the reference counts `Arc` keeps next to each node
+fn node_bytes(children: usize) -> usize {
+    mem::size_of::<Vlox>()
+        + 2 * mem::size_of::<usize>()
+        + children * mem::size_of::<Option<Arc<Vlox>>>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tiled_floor() -> VloxData {
+        // a checkerboard of two materials, each tile with the same notch in it
+        let mut data = VloxData::new(3);
+        for x in 0..8 {
+            for z in 0..8 {
+                data.set(x, 0, z, 3, 1 + ((x + z) % 2) as MaterialId);
+                data.set(x * 2, 0, z * 2, 4, 3);
+            }
+        }
+        data
+    }
+
+    fn shared(mut data: VloxData) -> (VloxData, VloxDag) {
+        let mut dag = VloxDag::default();
+        data.share(&mut dag);
+        (data, dag)
+    }
+
+    #[test]
+    fn dag_shares_identical_subtrees() {
+        let tree = tiled_floor();
+        let stats = tree.dag_stats();
+        assert_eq!(tree.node_count(), stats.tree_nodes);
+        assert_eq!(stats.tree_nodes, stats.unique_nodes);
+        assert_eq!(0, stats.saved_bytes());
+
+        let (data, _) = shared(tiled_floor());
+        let stats = data.dag_stats();
+        assert_eq!(data.node_count(), stats.tree_nodes);
+        assert!(stats.unique_nodes * 10 < stats.tree_nodes);
+        assert!(stats.saved_bytes() > 0);
+        assert_eq!(tree.root, data.root);
+    }
+
+    #[test]
+    fn edits_copy_shared_nodes() {
+        let (mut data, mut dag) = shared(tiled_floor());
+        let mut tree = tiled_floor();
+        data.set(0, 0, 0, 4, 5);
+        tree.set(0, 0, 0, 4, 5);
+        // the other tiles with the same notch keep theirs
+        assert_eq!(tree.root, data.root);
+        assert_eq!(3, data.get(2, 0, 0, 4));
+
+        // only the copies are visited, and putting the notch back shares the tile again
+        data.share(&mut dag);
+        assert_eq!(tree.root, data.root);
+        data.set(0, 0, 0, 4, 3);
+        data.share(&mut dag);
+        assert_eq!(shared(tiled_floor()).0.dag_stats(), data.dag_stats());
+        assert_eq!(tiled_floor().root, data.root);
+    }
+
+    #[test]
+    fn edits_at_the_root_replace_everything() {
+        let (mut data, mut dag) = shared(tiled_floor());
+        data.set(0, 0, 0, 0, 2);
+        data.share(&mut dag);
+        assert_eq!(1, data.dag_stats().unique_nodes);
+    }
+}
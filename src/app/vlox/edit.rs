@@ -0,0 +1,246 @@
+use std::sync::Arc;
+
+use super::{in_bounds, MaterialId, Vlox, VloxData, VOID};
+
+/// A leaf of the octree: x, y, z and depth of the vlox it fills, and its material
+pub type Leaf = (u128, u128, u128, u8, MaterialId);
+
+/// Largest region `VloxData::flood_fill` will fill
+pub const MAX_FLOOD_FILL: usize = 32_768;
+
+const NEIGHBOURS: [(i128, i128, i128); 6] = [
+    (-1, 0, 0),
+    (1, 0, 0),
+    (0, -1, 0),
+    (0, 1, 0),
+    (0, 0, -1),
+    (0, 0, 1),
+];
+
+impl VloxData {
+    /// Recolours every non-void leaf inside the vlox at `depth`, however deeply it is subdivided,
+    /// without changing its shape. With `surface_only` only leaves with a face open to void, or
+    /// to the edge of the data, are recoloured. Returns false if nothing was painted.
+    pub fn paint(
+        &mut self,
+        x: u128,
+        y: u128,
+        z: u128,
+        depth: u8,
+        value: MaterialId,
+        surface_only: bool,
+    ) -> bool {
+        let leaves: Vec<Leaf> = self
+            .leaves(x, y, z, depth)
+            .into_iter()
+            .filter(|&(_, _, _, _, id)| id != VOID && id != value)
+            .filter(|&(x, y, z, depth, _)| !surface_only || self.exposed(x, y, z, depth))
+            .collect();
+        for &(x, y, z, depth, _) in &leaves {
+            self.set(x, y, z, depth, value);
+        }
+        !leaves.is_empty()
+    }
+
+    /// Fills the face-connected vloxes at `depth` that have the start vlox's material with
+    /// `value`, returning the vloxes filled. Nothing is filled if there are more than
+    /// `MAX_FLOOD_FILL` of them, or if the start vlox already has `value`.
+    pub fn flood_fill(
+        &mut self,
+        (x, y, z): (u128, u128, u128),
+        depth: u8,
+        value: MaterialId,
+    ) -> Option<Vec<(u128, u128, u128)>> {
+        if self.get(x, y, z, depth) == value {
+            return Some(vec![]);
+        }
+        let region = self.flood_region(x, y, z, depth, MAX_FLOOD_FILL)?;
+        for &(x, y, z) in &region {
+            self.set(x, y, z, depth, value);
+        }
+        Some(region)
+    }
+
+    /// Replaces a material everywhere, then merges any subdivisions left with a single material
+    pub fn replace_material(&mut self, old: MaterialId, new: MaterialId) {
+        self.root.replace(old, new);
+        self.compact();
+    }
+
+    /// Merges vloxes whose children all have the same material, and drops children that
+    /// only repeat their parent's material
+    pub fn compact(&mut self) {
+        self.root.compact();
+    }
+
+    /// The leaves that make up the vlox at `depth`, which is a single leaf if it isn't
+    /// subdivided any further
+    fn leaves(&self, x: u128, y: u128, z: u128, depth: u8) -> Vec<Leaf> {
+        let mut vlox = &self.root;
+        for i in (0..depth).rev() {
+            let (cx, cy, cz) = ((x >> i) & 1, (y >> i) & 1, (z >> i) & 1);
+            match vlox.children.get((cx * 4 + cy * 2 + cz) as usize) {
+                Some(Some(child)) => vlox = child,
+                _ => return vec![(x, y, z, depth, vlox.value)],
+            }
+        }
+        let mut leaves = vec![];
+        vlox.leaves((x, y, z, depth), &mut leaves);
+        leaves
+    }
+
+    /// True if the vlox at `depth` shares a face with any void
+    fn exposed(&self, x: u128, y: u128, z: u128, depth: u8) -> bool {
+        let blocks = self.num_vlox(depth);
+        NEIGHBOURS.into_iter().any(|(dx, dy, dz)| {
+            let neighbour = (x as i128 + dx, y as i128 + dy, z as i128 + dz);
+            if !in_bounds(neighbour, blocks) {
+                return true;
+            }
+            let (x, y, z) = (
+                neighbour.0 as u128,
+                neighbour.1 as u128,
+                neighbour.2 as u128,
+            );
+            self.root
+                .any_leaf(self.xyz_to_path(x, y, z, depth), &|id| id == VOID)
+        })
+    }
+}
+
+impl Vlox {
+    fn replace(&mut self, old: MaterialId, new: MaterialId) {
+        if self.value == old {
+            self.value = new;
+        }
+        for child in self.children.iter_mut().flatten() {
+            Arc::make_mut(child).replace(old, new);
+        }
+        self.summarise();
+    }
+
+    pub(super) fn compact(&mut self) {
+        for child in self.children.iter_mut().flatten() {
+            Arc::make_mut(child).compact();
+        }
+        // None for children with finer detail
+        let values: Vec<Option<MaterialId>> = self
+            .children
+            .iter()
+            .map(|child| match child {
+                Some(child) if child.children.is_empty() => Some(child.value),
+                Some(_) => None,
+                None => Some(self.value),
+            })
+            .collect();
+        if let Some(Some(value)) = values.first() {
+            if values.iter().all(|v| *v == Some(*value)) {
+                *self = Vlox::uniform(*value);
+                return;
+            }
+        }
+        for child in self.children.iter_mut() {
+            if matches!(child, Some(c) if c.children.is_empty() && c.value == self.value) {
+                *child = None;
+            }
+        }
+        self.summarise();
+    }
+
+    pub(super) fn leaves(&self, (x, y, z, depth): (u128, u128, u128, u8), out: &mut Vec<Leaf>) {
+        if self.children.is_empty() {
+            out.push((x, y, z, depth, self.value));
+            return;
+        }
+        for (i, child) in self.children.iter().enumerate() {
+            let i = i as u128;
+            let key = (
+                x * 2 + ((i >> 2) & 1),
+                y * 2 + ((i >> 1) & 1),
+                z * 2 + (i & 1),
+                depth + 1,
+            );
+            match child {
+                Some(child) => child.leaves(key, out),
+                // children that were never created have this vlox's value
+                None => out.push((key.0, key.1, key.2, key.3, self.value)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paint_keeps_detail_and_void() {
+        let mut data = VloxData::new(2);
+        data.set(0, 0, 0, 1, 1);
+        // carve a notch out of the corner and add finer detail next to it
+        data.set(0, 0, 0, 3, VOID);
+        data.set(1, 0, 0, 3, 2);
+
+        assert!(data.paint(0, 0, 0, 1, 3, false));
+        assert_eq!(VOID, data.get(0, 0, 0, 3));
+        assert_eq!(3, data.get(1, 0, 0, 3));
+        assert_eq!(3, data.get(3, 3, 3, 3));
+        assert_eq!(VOID, data.get(2, 2, 2, 2));
+        // nothing left to paint
+        assert!(!data.paint(0, 0, 0, 1, 3, false));
+        assert!(!data.paint(3, 3, 3, 2, 1, false));
+    }
+
+    #[test]
+    fn paint_surface_only_skips_buried_leaves() {
+        let mut data = VloxData::new(2);
+        for x in 0..3 {
+            for y in 0..3 {
+                for z in 0..3 {
+                    data.set(x, y, z, 2, 1);
+                }
+            }
+        }
+
+        assert!(data.paint(0, 0, 0, 1, 2, true));
+        // the middle of the 3x3x3 cube is buried
+        assert_eq!(1, data.get(1, 1, 1, 2));
+        assert_eq!(2, data.get(0, 0, 0, 2));
+        assert_eq!(2, data.get(1, 1, 0, 2));
+    }
+
+    #[test]
+    fn flood_fill_stops_at_the_cap() {
+        let mut data = VloxData::new(2);
+        data.set(0, 0, 0, 1, 1);
+        assert_eq!(8, data.flood_fill((1, 1, 1), 2, 2).unwrap().len());
+        assert_eq!(2, data.get(0, 1, 0, 2));
+        assert_eq!(VOID, data.get(2, 0, 0, 2));
+        // filling with the same material again changes nothing
+        assert_eq!(Some(vec![]), data.flood_fill((1, 1, 1), 2, 2));
+
+        // the void around it at depth 8 is far too big
+        let mut data = VloxData::new(2);
+        data.set(0, 0, 0, 8, 1);
+        assert_eq!(None, data.flood_fill((255, 255, 255), 8, 1));
+        assert_eq!(VOID, data.get(255, 255, 255, 8));
+    }
+
+    #[test]
+    fn replace_material_compacts_the_tree() {
+        let mut data = VloxData::new(2);
+        data.set(0, 0, 0, 1, 1);
+        data.set(0, 0, 0, 3, 2);
+        data.set(1, 1, 1, 3, 2);
+        let nodes = data.node_count();
+
+        data.replace_material(2, 1);
+        assert_eq!(1, data.get(0, 0, 0, 3));
+        assert_eq!(1, data.get(1, 1, 1, 3));
+        assert!(data.node_count() < nodes);
+        assert_eq!(2, data.node_count());
+
+        data.replace_material(1, VOID);
+        assert_eq!(1, data.node_count());
+    }
+}
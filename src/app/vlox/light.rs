@@ -0,0 +1,332 @@
+use std::collections::VecDeque;
+
+use super::{in_bounds, Color, MaterialMap, VloxData};
+
+/// Light level of open sky and of emissive vloxes, each step through void loses one level
+pub const MAX_LIGHT: u8 = 15;
+/// Brightness of a vertex no light reaches at all
+const MIN_BRIGHTNESS: f32 = 0.08;
+
+const NEIGHBOURS: [(i128, i128, i128); 6] = [
+    (-1, 0, 0),
+    (1, 0, 0),
+    (0, -1, 0),
+    (0, 1, 0),
+    (0, 0, -1),
+    (0, 0, 1),
+];
+
+/// Skylight and block light flood-filled through the void vloxes of a `VloxData` at a fixed depth.
+///
+/// Vloxes subdivided deeper than the light depth let light through if any part of them is void,
+/// vloxes stored at shallower depths simply cover several light cells.
+#[derive(Default)]
+pub struct LightMap {
+    depth: u8,
+    blocks: u128,
+    transparent: Vec<bool>,
+    source: Vec<u8>,
+    sky: Vec<u8>,
+    block: Vec<u8>,
+}
+impl LightMap {
+    pub fn new(data: &VloxData, materials: &MaterialMap, depth: u8) -> Self {
+        let blocks = 2_u128.pow(depth as u32);
+        let cells = (blocks * blocks * blocks) as usize;
+        let mut light = Self {
+            depth,
+            blocks,
+            transparent: vec![false; cells],
+            source: vec![0; cells],
+            sky: vec![0; cells],
+            block: vec![0; cells],
+        };
+        let all = ((0, 0, 0), (blocks - 1, blocks - 1, blocks - 1));
+        light.sample(data, materials, all);
+        light.relight(all);
+        light
+    }
+    pub fn depth(&self) -> u8 {
+        self.depth
+    }
+    /// Brightest of skylight and block light at a cell of the light depth
+    pub fn level(&self, x: u128, y: u128, z: u128) -> u8 {
+        let i = self.index((x, y, z));
+        self.sky[i].max(self.block[i])
+    }
+
+    /// Relights after the vlox at `depth` has changed, only touching cells its light could reach
+    pub fn update(
+        &mut self,
+        data: &VloxData,
+        materials: &MaterialMap,
+        x: u128,
+        y: u128,
+        z: u128,
+        depth: u8,
+    ) {
+        if self.blocks == 0 {
+            return;
+        }
+        let changed = if depth <= self.depth {
+            let scale = 2_u128.pow((self.depth - depth) as u32);
+            (
+                (x * scale, y * scale, z * scale),
+                (
+                    (x + 1) * scale - 1,
+                    (y + 1) * scale - 1,
+                    (z + 1) * scale - 1,
+                ),
+            )
+        } else {
+            let shift = depth - self.depth;
+            let cell = (x >> shift, y >> shift, z >> shift);
+            (cell, cell)
+        };
+        self.sample(data, materials, changed);
+
+        // skylight falls down whole columns, so the region always reaches the bottom
+        let reach = MAX_LIGHT as u128;
+        let last = self.blocks - 1;
+        let (min, max) = changed;
+        self.relight((
+            (min.0.saturating_sub(reach), 0, min.2.saturating_sub(reach)),
+            ((max.0 + reach).min(last), last, (max.2 + reach).min(last)),
+        ));
+    }
+
+    /// Colour of a mesh vertex, lit by the void cells around it on the side its face points to
+    pub fn shade(
+        &self,
+        color: Color,
+        (x, y, z): (u128, u128, u128),
+        normal: [i128; 3],
+    ) -> [f32; 4] {
+        let vertex = [x as i128, y as i128, z as i128];
+        let axis = normal.iter().position(|n| *n != 0).unwrap_or(0);
+        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+
+        let mut total = 0.0;
+        let mut samples = 0.0;
+        for du in [-1, 0] {
+            for dv in [-1, 0] {
+                let mut cell = vertex;
+                if normal[axis] < 0 {
+                    cell[axis] -= 1;
+                }
+                cell[u] += du;
+                cell[v] += dv;
+                let cell = (cell[0], cell[1], cell[2]);
+                if !in_bounds(cell, self.blocks) {
+                    total += MAX_LIGHT as f32;
+                    samples += 1.0;
+                } else {
+                    let cell = (cell.0 as u128, cell.1 as u128, cell.2 as u128);
+                    if self.transparent[self.index(cell)] {
+                        total += self.level(cell.0, cell.1, cell.2) as f32;
+                        samples += 1.0;
+                    }
+                }
+            }
+        }
+
+        let level = if samples > 0.0 { total / samples } else { 0.0 };
+        let brightness = MIN_BRIGHTNESS + (1.0 - MIN_BRIGHTNESS) * level / MAX_LIGHT as f32;
+        let [r, g, b, a] = color.as_f32x4();
+        [r * brightness, g * brightness, b * brightness, a]
+    }
+
+    fn index(&self, (x, y, z): (u128, u128, u128)) -> usize {
+        ((x * self.blocks + y) * self.blocks + z) as usize
+    }
+
+    /// Reads which cells let light through and which emit it
+    fn sample(
+        &mut self,
+        data: &VloxData,
+        materials: &MaterialMap,
+        (min, max): ((u128, u128, u128), (u128, u128, u128)),
+    ) {
+        for x in min.0..=max.0 {
+            for y in min.1..=max.1 {
+                for z in min.2..=max.2 {
+                    let i = self.index((x, y, z));
+                    self.transparent[i] = data.contains_void(x, y, z, self.depth, materials);
+                    self.source[i] = if materials.emissive(data.get(x, y, z, self.depth)) > 0.0 {
+                        MAX_LIGHT
+                    } else {
+                        0
+                    };
+                }
+            }
+        }
+    }
+
+    /// Clears and flood-fills the light inside a region, keeping the light that enters from outside
+    fn relight(&mut self, (min, max): ((u128, u128, u128), (u128, u128, u128))) {
+        let inside = |(x, y, z): (i128, i128, i128)| {
+            x >= min.0 as i128
+                && y >= min.1 as i128
+                && z >= min.2 as i128
+                && x <= max.0 as i128
+                && y <= max.1 as i128
+                && z <= max.2 as i128
+        };
+
+        let mut sky = VecDeque::new();
+        let mut block = VecDeque::new();
+        for x in min.0..=max.0 {
+            for z in min.2..=max.2 {
+                // open sky reaches straight down until something blocks it
+                let mut open = max.1 == self.blocks - 1
+                    || self.sky[self.index((x, max.1 + 1, z))] == MAX_LIGHT;
+                for y in (min.1..=max.1).rev() {
+                    let i = self.index((x, y, z));
+                    open = open && self.transparent[i];
+                    self.sky[i] = if open { MAX_LIGHT } else { 0 };
+                    self.block[i] = self.source[i];
+                    if open {
+                        sky.push_back((x, y, z));
+                    }
+                    if self.source[i] > 0 {
+                        block.push_back((x, y, z));
+                    }
+                }
+            }
+        }
+
+        // light from just outside the region flows back in
+        for x in min.0..=max.0 {
+            for y in min.1..=max.1 {
+                for z in min.2..=max.2 {
+                    let on_edge = x == min.0
+                        || y == min.1
+                        || z == min.2
+                        || x == max.0
+                        || y == max.1
+                        || z == max.2;
+                    if !on_edge {
+                        continue;
+                    }
+                    for (dx, dy, dz) in NEIGHBOURS {
+                        let outside = (x as i128 + dx, y as i128 + dy, z as i128 + dz);
+                        if inside(outside) || !in_bounds(outside, self.blocks) {
+                            continue;
+                        }
+                        let outside = (outside.0 as u128, outside.1 as u128, outside.2 as u128);
+                        let i = self.index(outside);
+                        if self.sky[i] > 0 {
+                            sky.push_back(outside);
+                        }
+                        if self.block[i] > 0 {
+                            block.push_back(outside);
+                        }
+                    }
+                }
+            }
+        }
+
+        Self::propagate(self.blocks, &self.transparent, &mut self.sky, sky, &inside);
+        Self::propagate(
+            self.blocks,
+            &self.transparent,
+            &mut self.block,
+            block,
+            &inside,
+        );
+    }
+
+    fn propagate(
+        blocks: u128,
+        transparent: &[bool],
+        levels: &mut [u8],
+        mut queue: VecDeque<(u128, u128, u128)>,
+        inside: &impl Fn((i128, i128, i128)) -> bool,
+    ) {
+        let index = |(x, y, z): (u128, u128, u128)| ((x * blocks + y) * blocks + z) as usize;
+        while let Some(cell) = queue.pop_front() {
+            let level = levels[index(cell)];
+            if level <= 1 {
+                continue;
+            }
+            for (dx, dy, dz) in NEIGHBOURS {
+                let next = (
+                    cell.0 as i128 + dx,
+                    cell.1 as i128 + dy,
+                    cell.2 as i128 + dz,
+                );
+                if !in_bounds(next, blocks) || !inside(next) {
+                    continue;
+                }
+                let next = (next.0 as u128, next.1 as u128, next.2 as u128);
+                let i = index(next);
+                if transparent[i] && levels[i] < level - 1 {
+                    levels[i] = level - 1;
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Material, SolidMaterial};
+    use super::*;
+
+    fn materials() -> MaterialMap {
+        let mut materials = MaterialMap::default();
+        materials.set(0, Material::Void);
+        for (id, emissive) in [(1, 0.0), (2, 1.0)] {
+            materials.set(
+                id,
+                Material::Solid(SolidMaterial {
+                    name: id.to_string(),
+                    data: VloxData::new(0),
+                    colors: vec![Color::new(1.0, 1.0, 1.0, 1.0)],
+                    emissive,
+                    metallic: 0.0,
+                    roughness: 0.5,
+                }),
+            );
+        }
+        materials
+    }
+
+    #[test]
+    fn enclosed_rooms_are_dark_until_lit() {
+        let materials = materials();
+        let mut data = VloxData::new(2);
+        // hollow 4x4x4 box, with a finer subdivided wall on one side
+        for x in 0..4 {
+            for y in 0..4 {
+                for z in 0..4 {
+                    if x == 0 || y == 0 || z == 0 || x == 3 || y == 3 || z == 3 {
+                        data.set(x, y, z, 2, 1);
+                    }
+                }
+            }
+        }
+        data.set(6, 2, 2, 3, 1);
+
+        let mut light = LightMap::new(&data, &materials, 2);
+        assert_eq!(0, light.level(1, 1, 1));
+        assert_eq!(0, light.level(2, 2, 2));
+
+        // a lamp inside lights the room
+        data.set(1, 1, 1, 2, 2);
+        light.update(&data, &materials, 1, 1, 1, 2);
+        assert_eq!(MAX_LIGHT - 1, light.level(2, 1, 1));
+        assert_eq!(MAX_LIGHT - 3, light.level(2, 2, 2));
+
+        // opening the roof lets the sky in
+        data.set(4, 7, 4, 3, 0);
+        light.update(&data, &materials, 4, 7, 4, 3);
+        assert_eq!(MAX_LIGHT, light.level(2, 2, 2));
+        assert_eq!(MAX_LIGHT, light.level(2, 1, 2));
+
+        let full = LightMap::new(&data, &materials, 2);
+        assert_eq!(full.sky, light.sky);
+        assert_eq!(full.block, light.block);
+    }
+}
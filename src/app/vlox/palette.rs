@@ -0,0 +1,470 @@
+use std::{collections::HashSet, fmt};
+
+use serde::{Deserialize, Serialize};
+
+use super::{Color, Material, MaterialId, MaterialMap, SolidMaterial, VloxData};
+
+/// File formats a `MaterialMap` can be loaded from and saved to.
+///
+/// The native RON and JSON formats keep each material's id, so re-importing an exported palette
+/// doesn't change the materials of vloxes already placed. Entries without an id, and every entry
+/// of the other formats, take the lowest free ids from 1 in file order. Id 0 is always void.
+/// Only the native formats keep names and PBR fields.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PaletteFormat {
+    /// GIMP palette, `R G B name` per line
+    Gpl,
+    /// One `RRGGBB` colour per line
+    Hex,
+    /// Every distinct pixel colour, scanning rows left to right
+    Png,
+    Ron,
+    Json,
+}
+impl PaletteFormat {
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "gpl" => Some(Self::Gpl),
+            "hex" | "txt" => Some(Self::Hex),
+            "png" => Some(Self::Png),
+            "ron" => Some(Self::Ron),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum PaletteError {
+    /// A line of a text palette that couldn't be read
+    Line(usize, String),
+    Png(String),
+    Ron(String),
+    Json(String),
+    /// An id that is void or used twice
+    Id(MaterialId),
+    /// A material whose colour has a negative or non-finite channel
+    Color(String),
+    Empty,
+}
+impl fmt::Display for PaletteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PaletteError::Line(line, text) => write!(f, "invalid palette line {line}: {text:?}"),
+            PaletteError::Png(error) => write!(f, "invalid png palette: {error}"),
+            PaletteError::Ron(error) => write!(f, "invalid ron palette: {error}"),
+            PaletteError::Json(error) => write!(f, "invalid json palette: {error}"),
+            PaletteError::Id(id) => write!(f, "material id {id} is void or used twice"),
+            PaletteError::Color(name) => write!(f, "material {name:?} has an invalid colour"),
+            PaletteError::Empty => write!(f, "palette has no colours"),
+        }
+    }
+}
+impl std::error::Error for PaletteError {}
+
+/// The native palette format, written as RON or JSON
+#[derive(Serialize, Deserialize)]
+struct PaletteFile {
+    materials: Vec<PaletteMaterial>,
+}
+#[derive(Serialize, Deserialize)]
+struct PaletteMaterial {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<MaterialId>,
+    name: String,
+    /// Linear RGBA
+    color: [f32; 4],
+    #[serde(default)]
+    emissive: f32,
+    #[serde(default)]
+    metallic: f32,
+    #[serde(default = "default_roughness")]
+    roughness: f32,
+}
+fn default_roughness() -> f32 {
+    0.5
+}
+
+impl MaterialMap {
+    pub fn from_palette(bytes: &[u8], format: PaletteFormat) -> Result<Self, PaletteError> {
+        let materials = match format {
+            PaletteFormat::Gpl => read_gpl(&String::from_utf8_lossy(bytes))?,
+            PaletteFormat::Hex => read_hex(&String::from_utf8_lossy(bytes))?,
+            PaletteFormat::Png => read_png(bytes)?,
+            PaletteFormat::Ron => {
+                ron::de::from_bytes::<PaletteFile>(bytes)
+                    .map_err(|error| PaletteError::Ron(error.to_string()))?
+                    .materials
+            }
+            PaletteFormat::Json => {
+                serde_json::from_slice::<PaletteFile>(bytes)
+                    .map_err(|error| PaletteError::Json(error.to_string()))?
+                    .materials
+            }
+        };
+        if materials.is_empty() {
+            return Err(PaletteError::Empty);
+        }
+
+        let mut map = MaterialMap::default();
+        map.set(0, Material::Void);
+        for material in &materials {
+            if !material.color.iter().all(|c| c.is_finite() && *c >= 0.0) {
+                return Err(PaletteError::Color(material.name.clone()));
+            }
+            if let Some(id) = material.id {
+                if id == 0 || map.map.contains_key(&id) {
+                    return Err(PaletteError::Id(id));
+                }
+                // reserved until the material is added below
+                map.set(id, Material::Void);
+            }
+        }
+        let mut free = (1..=MaterialId::MAX).filter(|id| !map.map.contains_key(id));
+        let ids: Vec<MaterialId> = materials
+            .iter()
+            .map(|material| {
+                material
+                    .id
+                    .or_else(|| free.next())
+                    .ok_or(PaletteError::Id(MaterialId::MAX))
+            })
+            .collect::<Result<_, _>>()?;
+        for (id, material) in ids.into_iter().zip(materials) {
+            let [r, g, b, a] = material.color;
+            map.set(
+                id,
+                Material::Solid(SolidMaterial {
+                    name: material.name,
+                    data: VloxData::new(0),
+                    colors: vec![Color::new(r, g, b, a)],
+                    emissive: material.emissive,
+                    metallic: material.metallic,
+                    roughness: material.roughness,
+                }),
+            );
+        }
+        Ok(map)
+    }
+
+    pub fn to_palette(&self, format: PaletteFormat) -> Result<Vec<u8>, PaletteError> {
+        let mut ids: Vec<&MaterialId> = self.map.keys().collect();
+        ids.sort();
+        let materials: Vec<PaletteMaterial> = ids
+            .into_iter()
+            .filter_map(|id| match &self.map[id] {
+                // a material without colours isn't drawn, so there is nothing to export
+                Material::Solid(material) => Some(PaletteMaterial {
+                    id: Some(*id),
+                    name: material.name.clone(),
+                    color: material.colors.first()?.as_f32x4(),
+                    emissive: material.emissive,
+                    metallic: material.metallic,
+                    roughness: material.roughness,
+                }),
+                _ => None,
+            })
+            .collect();
+        if materials.is_empty() {
+            return Err(PaletteError::Empty);
+        }
+
+        Ok(match format {
+            PaletteFormat::Gpl => {
+                let mut gpl = format!("GIMP Palette\nColumns: {}\n#\n", materials.len().min(16));
+                for material in &materials {
+                    let [r, g, b, _] = to_srgb8(material.color);
+                    gpl += &format!("{r:3} {g:3} {b:3}\t{}\n", material.name);
+                }
+                gpl.into_bytes()
+            }
+            PaletteFormat::Hex => materials
+                .iter()
+                .map(|material| {
+                    let [r, g, b, _] = to_srgb8(material.color);
+                    format!("{r:02x}{g:02x}{b:02x}\n")
+                })
+                .collect::<String>()
+                .into_bytes(),
+            PaletteFormat::Png => write_png(&materials)?,
+            PaletteFormat::Ron => ron::ser::to_string_pretty(
+                &PaletteFile { materials },
+                ron::ser::PrettyConfig::default(),
+            )
+            .map_err(|error| PaletteError::Ron(error.to_string()))?
+            .into_bytes(),
+            PaletteFormat::Json => serde_json::to_vec_pretty(&PaletteFile { materials })
+                .map_err(|error| PaletteError::Json(error.to_string()))?,
+        })
+    }
+}
+
+fn read_gpl(text: &str) -> Result<Vec<PaletteMaterial>, PaletteError> {
+    let mut materials = vec![];
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if i == 0 && line == "GIMP Palette" {
+            continue;
+        }
+        if line.is_empty()
+            || line.starts_with('#')
+            || line.starts_with("Name:")
+            || line.starts_with("Columns:")
+        {
+            continue;
+        }
+
+        let mut parts = line.split_whitespace();
+        let mut channel = || {
+            parts
+                .next()
+                .and_then(|part| part.parse::<u8>().ok())
+                .ok_or_else(|| PaletteError::Line(i + 1, line.to_string()))
+        };
+        let srgb = [channel()?, channel()?, channel()?, 255];
+        let name = parts.collect::<Vec<_>>().join(" ");
+        materials.push(PaletteMaterial {
+            name: if name.is_empty() {
+                hex_name(srgb)
+            } else {
+                name
+            },
+            ..PaletteMaterial::from_srgb8(srgb)
+        });
+    }
+    Ok(materials)
+}
+
+fn read_hex(text: &str) -> Result<Vec<PaletteMaterial>, PaletteError> {
+    let mut materials = vec![];
+    for (i, line) in text.lines().enumerate() {
+        let hex = line.trim().trim_start_matches('#');
+        if hex.is_empty() || hex.starts_with(';') {
+            continue;
+        }
+        let channel = |at: usize| {
+            hex.get(at..at + 2)
+                .and_then(|channel| u8::from_str_radix(channel, 16).ok())
+                .ok_or_else(|| PaletteError::Line(i + 1, line.to_string()))
+        };
+        if hex.len() != 6 && hex.len() != 8 {
+            return Err(PaletteError::Line(i + 1, line.to_string()));
+        }
+        let alpha = if hex.len() == 8 { channel(6)? } else { 255 };
+        materials.push(PaletteMaterial::from_srgb8([
+            channel(0)?,
+            channel(2)?,
+            channel(4)?,
+            alpha,
+        ]));
+    }
+    Ok(materials)
+}
+
+fn read_png(bytes: &[u8]) -> Result<Vec<PaletteMaterial>, PaletteError> {
+    let png_error = |error: png::DecodingError| PaletteError::Png(error.to_string());
+
+    let mut decoder = png::Decoder::new(bytes);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(png_error)?;
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).map_err(png_error)?;
+
+    let mut seen = HashSet::new();
+    let mut materials = vec![];
+    for row in pixels.chunks(info.line_size).take(info.height as usize) {
+        let row = &row[..info.width as usize * info.color_type.samples()];
+        for pixel in row.chunks(info.color_type.samples()) {
+            let srgb = match *pixel {
+                [v] => [v, v, v, 255],
+                [v, a] => [v, v, v, a],
+                [r, g, b] => [r, g, b, 255],
+                [r, g, b, a] => [r, g, b, a],
+                _ => continue,
+            };
+            if seen.insert(srgb) {
+                materials.push(PaletteMaterial::from_srgb8(srgb));
+            }
+        }
+    }
+    Ok(materials)
+}
+
+/// A one pixel high strip with a pixel per material
+fn write_png(materials: &[PaletteMaterial]) -> Result<Vec<u8>, PaletteError> {
+    let png_error = |error: png::EncodingError| PaletteError::Png(error.to_string());
+
+    let mut bytes = vec![];
+    let mut encoder = png::Encoder::new(&mut bytes, materials.len() as u32, 1);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(png_error)?;
+    let pixels: Vec<u8> = materials
+        .iter()
+        .flat_map(|material| to_srgb8(material.color))
+        .collect();
+    writer.write_image_data(&pixels).map_err(png_error)?;
+    writer.finish().map_err(png_error)?;
+    Ok(bytes)
+}
+
+impl PaletteMaterial {
+    fn from_srgb8(srgb: [u8; 4]) -> Self {
+        let linear = |channel: u8| {
+            let c = channel as f32 / 255.0;
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        Self {
+            id: None,
+            name: hex_name(srgb),
+            color: [
+                linear(srgb[0]),
+                linear(srgb[1]),
+                linear(srgb[2]),
+                srgb[3] as f32 / 255.0,
+            ],
+            emissive: 0.0,
+            metallic: 0.0,
+            roughness: default_roughness(),
+        }
+    }
+}
+
+fn to_srgb8([r, g, b, a]: [f32; 4]) -> [u8; 4] {
+    let srgb = |c: f32| {
+        let c = c.clamp(0.0, 1.0);
+        let c = if c <= 0.0031308 {
+            c * 12.92
+        } else {
+            1.055 * c.powf(1.0 / 2.4) - 0.055
+        };
+        (c * 255.0).round() as u8
+    };
+    [
+        srgb(r),
+        srgb(g),
+        srgb(b),
+        (a.clamp(0.0, 1.0) * 255.0).round() as u8,
+    ]
+}
+
+fn hex_name([r, g, b, _]: [u8; 4]) -> String {
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(map: &MaterialMap) -> Vec<String> {
+        (1..)
+            .map_while(|id| match map.get(id) {
+                Some(Material::Solid(material)) => Some(material.name.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn read_text_palettes() {
+        let gpl = "GIMP Palette\nName: Test\nColumns: 2\n#\n255   0   0\tRed\n  0 128 255\n";
+        let map = MaterialMap::from_palette(gpl.as_bytes(), PaletteFormat::Gpl).unwrap();
+        assert_eq!(vec!["Red", "#0080ff"], names(&map));
+        assert!(matches!(map.get(0), Some(Material::Void)));
+
+        let hex = "ff0000\n#0080ff\n\n";
+        let map = MaterialMap::from_palette(hex.as_bytes(), PaletteFormat::Hex).unwrap();
+        assert_eq!(vec!["#ff0000", "#0080ff"], names(&map));
+
+        assert!(matches!(
+            MaterialMap::from_palette(b"ff00\n", PaletteFormat::Hex),
+            Err(PaletteError::Line(1, _))
+        ));
+    }
+
+    #[test]
+    fn palettes_round_trip() {
+        let gpl = "GIMP Palette\n255 0 0 Red\n0 128 255 Sky\n12 34 56 Navy\n";
+        let map = MaterialMap::from_palette(gpl.as_bytes(), PaletteFormat::Gpl).unwrap();
+
+        for format in [
+            PaletteFormat::Gpl,
+            PaletteFormat::Hex,
+            PaletteFormat::Png,
+            PaletteFormat::Ron,
+            PaletteFormat::Json,
+        ] {
+            let bytes = map.to_palette(format).unwrap();
+            let loaded = MaterialMap::from_palette(&bytes, format).unwrap();
+            let hex = String::from_utf8(loaded.to_palette(PaletteFormat::Hex).unwrap()).unwrap();
+            assert_eq!("ff0000\n0080ff\n0c2238\n", hex, "{format:?}");
+        }
+
+        let ron = map.to_palette(PaletteFormat::Ron).unwrap();
+        let loaded = MaterialMap::from_palette(&ron, PaletteFormat::Ron).unwrap();
+        assert_eq!(vec!["Red", "Sky", "Navy"], names(&loaded));
+    }
+
+    #[test]
+    fn native_palettes_keep_ids() {
+        let mut map = MaterialMap::from_palette(b"ff0000\n0080ff\n", PaletteFormat::Hex).unwrap();
+        // a gap where a material was removed, and one without colours
+        map.map.remove(&1);
+        map.set(
+            4,
+            Material::Solid(SolidMaterial {
+                name: "Blank".to_string(),
+                data: VloxData::new(0),
+                colors: vec![],
+                emissive: 0.0,
+                metallic: 0.0,
+                roughness: 0.5,
+            }),
+        );
+
+        for format in [PaletteFormat::Ron, PaletteFormat::Json] {
+            let bytes = map.to_palette(format).unwrap();
+            let loaded = MaterialMap::from_palette(&bytes, format).unwrap();
+            assert_eq!(vec![2], loaded.ids(), "{format:?}");
+            assert_eq!(Some("#0080ff"), loaded.name(2));
+        }
+
+        // entries without ids fill the gaps
+        let json = r#"{"materials": [
+            {"name": "A", "color": [1, 0, 0, 1]},
+            {"id": 1, "name": "B", "color": [0, 1, 0, 1]},
+            {"name": "C", "color": [0, 0, 1, 1]}
+        ]}"#;
+        let loaded = MaterialMap::from_palette(json.as_bytes(), PaletteFormat::Json).unwrap();
+        assert_eq!(vec![1, 2, 3], loaded.ids());
+        assert_eq!(Some("B"), loaded.name(1));
+        assert_eq!(Some("A"), loaded.name(2));
+    }
+
+    #[test]
+    fn invalid_native_palettes_are_rejected() {
+        let palette = |materials: &str| {
+            let json = format!(r#"{{"materials": [{materials}]}}"#);
+            MaterialMap::from_palette(json.as_bytes(), PaletteFormat::Json)
+        };
+        assert!(matches!(
+            palette(r#"{"id": 0, "name": "A", "color": [1, 0, 0, 1]}"#),
+            Err(PaletteError::Id(0))
+        ));
+        assert!(matches!(
+            palette(
+                r#"{"id": 3, "name": "A", "color": [1, 0, 0, 1]},
+                   {"id": 3, "name": "B", "color": [1, 0, 0, 1]}"#
+            ),
+            Err(PaletteError::Id(3))
+        ));
+        assert!(matches!(
+            palette(r#"{"name": "A", "color": [1, -1, 0, 1]}"#),
+            Err(PaletteError::Color(_))
+        ));
+    }
+}
@@ -0,0 +1,365 @@
+use std::{fmt, sync::Arc};
+
+use super::{MaterialId, Vlox, VloxData};
+
+/// First bytes of a serialised `VloxPatch`
+const PATCH_MAGIC: &[u8; 4] = b"VXP1";
+/// Deepest vlox a read patch can reach, so positions fit in a u128
+const MAX_PATCH_DEPTH: u8 = 127;
+
+/// The subtrees that differ between two versions of a `VloxData`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VloxPatch {
+    pub changes: Vec<SubtreeChange>,
+}
+
+/// A vlox that changed, with its whole subtree before and after
+#[derive(Clone, Debug, PartialEq)]
+pub struct SubtreeChange {
+    pub x: u128,
+    pub y: u128,
+    pub z: u128,
+    pub depth: u8,
+    old: Vlox,
+    new: Vlox,
+}
+impl SubtreeChange {
+    /// The material before the change, or None if the vlox was subdivided
+    pub fn old_value(&self) -> Option<MaterialId> {
+        self.old.is_uniform().then_some(self.old.value)
+    }
+    /// The material after the change, or None if the vlox is subdivided
+    pub fn new_value(&self) -> Option<MaterialId> {
+        self.new.is_uniform().then_some(self.new.value)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PatchError {
+    /// Doesn't start with `PATCH_MAGIC`
+    NotAPatch,
+    /// Ends in the middle of a change
+    Truncated,
+    /// A change, or a vlox inside its subtrees, is deeper than `MAX_PATCH_DEPTH`
+    TooDeep,
+    /// A change is outside the data at its depth
+    OutOfBounds,
+    /// A material id that doesn't fit in a `MaterialId`
+    Material(u128),
+}
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PatchError::NotAPatch => write!(f, "not a vlox patch"),
+            PatchError::Truncated => write!(f, "vlox patch is truncated"),
+            PatchError::TooDeep => write!(f, "vlox patch is deeper than {MAX_PATCH_DEPTH}"),
+            PatchError::OutOfBounds => write!(f, "vlox patch changes vloxes outside the data"),
+            PatchError::Material(id) => write!(f, "vlox patch has invalid material {id}"),
+        }
+    }
+}
+impl std::error::Error for PatchError {}
+
+impl VloxData {
+    /// The patch that turns this data into `other`. Subtrees that are the same in both are
+    /// skipped whole, and a change is recorded where the two stop having the same shape.
+    pub fn diff(&self, other: &VloxData) -> VloxPatch {
+        let mut patch = VloxPatch::default();
+        diff(&self.root, &other.root, (0, 0, 0, 0), &mut patch.changes);
+        patch
+    }
+    /// Replaces each changed subtree with its new version
+    pub fn apply(&mut self, patch: &VloxPatch) {
+        for change in &patch.changes {
+            let path = self.xyz_to_path(change.x, change.y, change.z, change.depth);
+            self.root.set_subtree(&path, change.new.clone());
+        }
+    }
+}
+
+impl VloxPatch {
+    /// The patch that undoes this one
+    pub fn invert(&self) -> VloxPatch {
+        VloxPatch {
+            changes: self
+                .changes
+                .iter()
+                .map(|change| SubtreeChange {
+                    old: change.new.clone(),
+                    new: change.old.clone(),
+                    ..change.clone()
+                })
+                .collect(),
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Binary form: the magic, the number of changes, then for each change its position as
+    /// varints, its depth and both subtrees. Subtrees are written depth first, each vlox as its
+    /// material, then 0 for a leaf or 1 and a byte with a bit set for each stored child.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = PATCH_MAGIC.to_vec();
+        write_varint(&mut bytes, self.changes.len() as u128);
+        for change in &self.changes {
+            write_varint(&mut bytes, change.x);
+            write_varint(&mut bytes, change.y);
+            write_varint(&mut bytes, change.z);
+            bytes.push(change.depth);
+            write_vlox(&mut bytes, &change.old);
+            write_vlox(&mut bytes, &change.new);
+        }
+        bytes
+    }
+    /// Reads a patch written by `to_bytes`, checking that every change is inside the data and
+    /// no deeper than `MAX_PATCH_DEPTH`, so files from anywhere can be applied safely
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, PatchError> {
+        let mut bytes = bytes
+            .strip_prefix(PATCH_MAGIC)
+            .ok_or(PatchError::NotAPatch)?;
+        let count = read_varint(&mut bytes)?;
+        let mut changes = vec![];
+        for _ in 0..count {
+            let (x, y, z) = (
+                read_varint(&mut bytes)?,
+                read_varint(&mut bytes)?,
+                read_varint(&mut bytes)?,
+            );
+            let depth = read_byte(&mut bytes)?;
+            if depth > MAX_PATCH_DEPTH {
+                return Err(PatchError::TooDeep);
+            }
+            if [x, y, z].into_iter().any(|i| i >> depth != 0) {
+                return Err(PatchError::OutOfBounds);
+            }
+            let levels = MAX_PATCH_DEPTH - depth;
+            changes.push(SubtreeChange {
+                x,
+                y,
+                z,
+                depth,
+                old: read_vlox(&mut bytes, levels)?,
+                new: read_vlox(&mut bytes, levels)?,
+            });
+        }
+        Ok(VloxPatch { changes })
+    }
+}
+
+/// Records where `new` differs from `old`, both being the vlox at the given position
+fn diff(
+    old: &Vlox,
+    new: &Vlox,
+    (x, y, z, depth): (u128, u128, u128, u8),
+    changes: &mut Vec<SubtreeChange>,
+) {
+    if old.is_uniform() && new.is_uniform() && old.value == new.value {
+        return;
+    }
+    if old.is_uniform() || new.is_uniform() {
+        changes.push(SubtreeChange {
+            x,
+            y,
+            z,
+            depth,
+            old: old.clone(),
+            new: new.clone(),
+        });
+        return;
+    }
+    for i in 0..8 {
+        // copies of the data share the nodes neither side edited since
+        if let (Some(old), Some(new)) = (&old.children[i], &new.children[i]) {
+            if Arc::ptr_eq(old, new) {
+                continue;
+            }
+        }
+        let key = (
+            x * 2 + ((i >> 2) & 1) as u128,
+            y * 2 + ((i >> 1) & 1) as u128,
+            z * 2 + (i & 1) as u128,
+            depth + 1,
+        );
+        // children that were never created are leaves with their parent's value
+        let old_leaf;
+        let old_child = match &old.children[i] {
+            Some(child) => child,
+            None => {
+                old_leaf = Vlox::uniform(old.value);
+                &old_leaf
+            }
+        };
+        let new_leaf;
+        let new_child = match &new.children[i] {
+            Some(child) => child,
+            None => {
+                new_leaf = Vlox::uniform(new.value);
+                &new_leaf
+            }
+        };
+        if old_child != new_child {
+            diff(old_child, new_child, key, changes);
+        }
+    }
+}
+
+impl Vlox {
+    /// Whether the vlox has no detail below it, stored or not
+    fn is_uniform(&self) -> bool {
+        self.children.iter().all(Option::is_none)
+    }
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u128) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+fn read_varint(bytes: &mut &[u8]) -> Result<u128, PatchError> {
+    let mut value = 0;
+    for shift in (0..128).step_by(7) {
+        let byte = read_byte(bytes)?;
+        value |= ((byte & 0x7f) as u128) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(PatchError::NotAPatch)
+}
+fn read_byte(bytes: &mut &[u8]) -> Result<u8, PatchError> {
+    let (byte, rest) = bytes.split_first().ok_or(PatchError::Truncated)?;
+    *bytes = rest;
+    Ok(*byte)
+}
+
+fn write_vlox(bytes: &mut Vec<u8>, vlox: &Vlox) {
+    write_varint(bytes, vlox.value as u128);
+    if vlox.children.is_empty() {
+        bytes.push(0);
+        return;
+    }
+    let mask = vlox
+        .children
+        .iter()
+        .enumerate()
+        .filter(|(_, child)| child.is_some())
+        .fold(0, |mask, (i, _)| mask | (1 << i));
+    bytes.extend([1, mask]);
+    for child in vlox.children.iter().flatten() {
+        write_vlox(bytes, child);
+    }
+}
+/// Reads a subtree that can go `levels` deeper than the vlox it starts at
+fn read_vlox(bytes: &mut &[u8], levels: u8) -> Result<Vlox, PatchError> {
+    let value = read_varint(bytes)?;
+    let value = MaterialId::try_from(value).map_err(|_| PatchError::Material(value))?;
+    if read_byte(bytes)? == 0 {
+        return Ok(Vlox::uniform(value));
+    }
+    let levels = levels.checked_sub(1).ok_or(PatchError::TooDeep)?;
+    let mask = read_byte(bytes)?;
+    let mut children = vec![None; 8];
+    for (i, child) in children.iter_mut().enumerate() {
+        if mask & (1 << i) != 0 {
+            *child = Some(Arc::new(read_vlox(bytes, levels)?));
+        }
+    }
+    let mut vlox = Vlox::new(value);
+    vlox.children = children;
+    vlox.summarise();
+    Ok(vlox)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_apply_and_invert_round_trip() {
+        let mut old = VloxData::new(2);
+        old.set(0, 0, 0, 1, 1);
+        old.set(3, 3, 3, 2, 2);
+        let mut new = old.clone();
+        new.set(1, 1, 1, 3, 3);
+        new.set(3, 3, 3, 2, 0);
+        new.set(1, 0, 0, 1, 4);
+
+        let patch = old.diff(&new);
+        // unchanged subtrees aren't visited, changes are as deep as they need to be
+        let mut changed: Vec<_> = patch
+            .changes
+            .iter()
+            .map(|c| ((c.x, c.y, c.z, c.depth), c.old_value(), c.new_value()))
+            .collect();
+        changed.sort();
+        assert_eq!(
+            vec![
+                ((0, 0, 0, 1), Some(1), None),
+                ((1, 0, 0, 1), Some(0), Some(4)),
+                ((3, 3, 3, 2), Some(2), Some(0)),
+            ],
+            changed
+        );
+
+        let mut patched = old.clone();
+        patched.apply(&patch);
+        assert!(patched.diff(&new).is_empty());
+        patched.apply(&patch.invert());
+        assert!(patched.diff(&old).is_empty());
+    }
+
+    #[test]
+    fn patches_serialise_compactly() {
+        let mut old = VloxData::new(2);
+        old.set(0, 0, 0, 1, 1);
+        let mut new = old.clone();
+        new.set(1, 1, 1, 3, 300);
+
+        let patch = old.diff(&new);
+        let bytes = patch.to_bytes();
+        assert!(bytes.len() < 32);
+        assert_eq!(Ok(patch), VloxPatch::from_bytes(&bytes));
+        assert_eq!(
+            Err(PatchError::Truncated),
+            VloxPatch::from_bytes(&bytes[..bytes.len() - 1])
+        );
+        assert_eq!(Err(PatchError::NotAPatch), VloxPatch::from_bytes(b"nope"));
+    }
+
+    #[test]
+    fn malformed_patches_are_rejected() {
+        // one change at (x, 0, 0) and `depth`, from void to `new`
+        let patch = |x: u128, depth: u8, new: &[u8]| {
+            let mut bytes = PATCH_MAGIC.to_vec();
+            write_varint(&mut bytes, 1);
+            write_varint(&mut bytes, x);
+            bytes.extend([0, 0, depth, 0, 0]);
+            bytes.extend(new);
+            VloxPatch::from_bytes(&bytes)
+        };
+        assert!(patch(3, 2, &[1, 0]).is_ok());
+        assert_eq!(Err(PatchError::OutOfBounds), patch(4, 2, &[1, 0]));
+        assert_eq!(Err(PatchError::TooDeep), patch(0, 200, &[1, 0]));
+        assert_eq!(
+            Err(PatchError::Material(70_000)),
+            patch(0, 2, &[0xf0, 0xa2, 0x04, 0])
+        );
+
+        // a subtree nesting a child in the first corner past the deepest allowed vlox
+        let mut nested = vec![];
+        for _ in 0..(MAX_PATCH_DEPTH - 2) {
+            nested.extend([1, 1, 1]);
+        }
+        nested.extend([1, 0]);
+        assert!(patch(0, 2, &nested).is_ok());
+        nested.splice(0..0, [1, 1, 1]);
+        assert_eq!(Err(PatchError::TooDeep), patch(0, 2, &nested));
+    }
+}
@@ -0,0 +1,139 @@
+use std::sync::Arc;
+
+use super::{MaterialId, SubVlox, Vlox, VloxData, VloxSummary, VOID};
+
+/// Which material a subtree becomes when it is collapsed into a single leaf
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PruneRule {
+    /// What the subtree stands for when looked at whole, see `VloxSummary::value`
+    Majority,
+    /// Its main non-void material if at least this fraction of it is filled, void if not.
+    /// Low thresholds keep thin detail solid.
+    Occupancy(f32),
+}
+impl PruneRule {
+    fn value(self, summary: VloxSummary) -> MaterialId {
+        match self {
+            PruneRule::Majority => summary.value(),
+            PruneRule::Occupancy(threshold) if summary.occupancy >= threshold => summary.material,
+            PruneRule::Occupancy(_) => VOID,
+        }
+    }
+}
+
+impl VloxData {
+    /// Collapses every subtree below `depth` into a single leaf at `depth`, returning the number
+    /// of nodes removed
+    pub fn prune_below(&mut self, depth: u8, rule: PruneRule) -> usize {
+        self.prune_region((0, 0, 0), 0, depth, rule)
+    }
+    /// Like `prune_below`, but only inside the vlox at `region_depth`
+    pub fn prune_region(
+        &mut self,
+        (x, y, z): (u128, u128, u128),
+        region_depth: u8,
+        depth: u8,
+        rule: PruneRule,
+    ) -> usize {
+        let path = self.xyz_to_path(x, y, z, region_depth);
+        self.root
+            .prune_at(&path, depth.saturating_sub(region_depth), rule)
+    }
+    /// Number of nodes `prune_region` would remove
+    pub fn pruned_node_count(
+        &self,
+        (x, y, z): (u128, u128, u128),
+        region_depth: u8,
+        depth: u8,
+    ) -> usize {
+        let mut vlox = &self.root;
+        for i in self.xyz_to_path(x, y, z, region_depth) {
+            match vlox.children.get(i as usize) {
+                Some(Some(child)) => vlox = child,
+                _ => return 0,
+            }
+        }
+        vlox.nodes_below(depth.saturating_sub(region_depth))
+    }
+}
+
+impl Vlox {
+    /// Prunes the vlox at the end of the path, `levels` below it
+    fn prune_at(&mut self, path: &[SubVlox], levels: u8, rule: PruneRule) -> usize {
+        let Some((&first, rest)) = path.split_first() else {
+            return self.prune(levels, rule);
+        };
+        let removed = match self.children.get_mut(first as usize) {
+            Some(Some(child)) => Arc::make_mut(child).prune_at(rest, levels, rule),
+            // past the stored detail there is nothing to prune
+            _ => return 0,
+        };
+        self.summarise();
+        removed
+    }
+    fn prune(&mut self, levels: u8, rule: PruneRule) -> usize {
+        if levels == 0 {
+            let removed = self.node_count() - 1;
+            *self = Vlox::uniform(rule.value(self.summary));
+            return removed;
+        }
+        let removed = self
+            .children
+            .iter_mut()
+            .flatten()
+            .map(|child| Arc::make_mut(child).prune(levels - 1, rule))
+            .sum();
+        self.summarise();
+        removed
+    }
+    /// Number of nodes more than `levels` below this one
+    fn nodes_below(&self, levels: u8) -> usize {
+        if levels == 0 {
+            return self.node_count() - 1;
+        }
+        self.children
+            .iter()
+            .flatten()
+            .map(|child| child.nodes_below(levels - 1))
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detailed() -> VloxData {
+        // a solid cell at depth 1 with a notch, and a single small vlox in another cell
+        let mut data = VloxData::new(2);
+        data.set(0, 0, 0, 1, 1);
+        data.set(0, 0, 0, 3, VOID);
+        data.set(7, 7, 7, 3, 2);
+        data
+    }
+
+    #[test]
+    fn prune_below_collapses_detail_by_rule() {
+        let mut data = detailed();
+        let nodes = data.node_count();
+        let count = data.pruned_node_count((0, 0, 0), 0, 1);
+        assert_eq!(count, data.prune_below(1, PruneRule::Majority));
+        assert_eq!(nodes - count, data.node_count());
+        assert_eq!((1, 1), data.leaf(0, 0, 0, 3));
+        assert_eq!((VOID, 1), data.leaf(7, 7, 7, 3));
+
+        let mut data = detailed();
+        data.prune_below(1, PruneRule::Occupancy(0.01));
+        assert_eq!((2, 1), data.leaf(7, 7, 7, 3));
+    }
+
+    #[test]
+    fn prune_region_leaves_the_rest_alone() {
+        let mut data = detailed();
+        let removed = data.prune_region((1, 1, 1), 1, 2, PruneRule::Majority);
+        assert_eq!(1, removed);
+        assert_eq!(VOID, data.get(7, 7, 7, 3));
+        assert_eq!((VOID, 3), data.leaf(0, 0, 0, 3));
+        assert_eq!(0, data.pruned_node_count((1, 1, 1), 1, 2));
+    }
+}
@@ -0,0 +1,410 @@
+use super::{ClipPlane, MaterialId, Vlox, VloxData, VOID};
+
+/// Vlox coordinates at some depth
+type VloxXyz = (u128, u128, u128);
+
+/// A face of a vlox, named after the direction it faces
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Face {
+    NegX,
+    PosX,
+    NegY,
+    PosY,
+    NegZ,
+    PosZ,
+}
+impl Face {
+    pub fn normal(&self) -> (f32, f32, f32) {
+        match self {
+            Face::NegX => (-1.0, 0.0, 0.0),
+            Face::PosX => (1.0, 0.0, 0.0),
+            Face::NegY => (0.0, -1.0, 0.0),
+            Face::PosY => (0.0, 1.0, 0.0),
+            Face::NegZ => (0.0, 0.0, -1.0),
+            Face::PosZ => (0.0, 0.0, 1.0),
+        }
+    }
+    /// The face a ray travelling along `axis` enters through
+    fn entered(axis: usize, direction: f32) -> Self {
+        match (axis, direction > 0.0) {
+            (0, true) => Face::NegX,
+            (0, false) => Face::PosX,
+            (1, true) => Face::NegY,
+            (1, false) => Face::PosY,
+            (_, true) => Face::NegZ,
+            (_, false) => Face::PosZ,
+        }
+    }
+}
+
+/// The first non-void vlox along a ray
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RaycastHit {
+    pub x: u128,
+    pub y: u128,
+    pub z: u128,
+    /// Depth the vlox is stored at, or the `max_depth` of the raycast if it is stored deeper
+    pub depth: u8,
+    pub value: MaterialId,
+    /// Face the ray entered the vlox through
+    pub face: Face,
+    /// Distance along the (normalized) ray to the entry point
+    pub distance: f32,
+}
+
+impl RaycastHit {
+    /// The vlox at `depth` that was hit, and the one in front of the face the ray entered
+    /// through, None if that is outside the data. Both come from the hit's key, so they are
+    /// exact at any depth or angle. `point` is where the ray entered, which only decides which
+    /// of the vloxes a bigger leaf holds was hit across the face.
+    pub fn cells(
+        &self,
+        data: &VloxData,
+        point: (f32, f32, f32),
+        depth: u8,
+    ) -> (VloxXyz, Option<VloxXyz>) {
+        let key = [self.x, self.y, self.z];
+        let normal = self.face.normal();
+        let normal = [normal.0, normal.1, normal.2];
+        let mut target = [0; 3];
+        if self.depth >= depth {
+            for axis in 0..3 {
+                target[axis] = key[axis] >> (self.depth - depth);
+            }
+        } else {
+            // the hit leaf holds many vloxes at `depth`, the ray entered the layer next to the face
+            let shift = depth - self.depth;
+            let (x, y, z) = data.xyz_f32_to_vlox_xyz(point.0, point.1, point.2, depth);
+            for (axis, index) in [x, y, z].into_iter().enumerate() {
+                let first = key[axis] << shift;
+                let last = first + (1 << shift) - 1;
+                target[axis] = match normal[axis] {
+                    n if n > 0.0 => last,
+                    n if n < 0.0 => first,
+                    _ => index.clamp(first, last),
+                };
+            }
+        }
+
+        let num_vlox = data.num_vlox(depth);
+        let mut in_front = Some(target);
+        for axis in 0..3 {
+            in_front = in_front.and_then(|mut cell| {
+                cell[axis] = match normal[axis] {
+                    n if n > 0.0 => cell[axis].checked_add(1).filter(|i| *i < num_vlox)?,
+                    n if n < 0.0 => cell[axis].checked_sub(1)?,
+                    _ => cell[axis],
+                };
+                Some(cell)
+            });
+        }
+        (
+            (target[0], target[1], target[2]),
+            in_front.map(|cell| (cell[0], cell[1], cell[2])),
+        )
+    }
+}
+
+struct Ray {
+    origin: [f32; 3],
+    direction: [f32; 3],
+    inverse: [f32; 3],
+    clip: Option<ClipPlane>,
+}
+impl Ray {
+    /// Where the ray enters and leaves an axis aligned cube, and the axis it enters along
+    fn slab(&self, min: [f32; 3], size: f32) -> (f32, f32, usize) {
+        self.slab_box(min, min.map(|min| min + size))
+    }
+    fn slab_box(&self, min: [f32; 3], max: [f32; 3]) -> (f32, f32, usize) {
+        let mut enter = f32::NEG_INFINITY;
+        let mut exit = f32::INFINITY;
+        let mut axis = 0;
+        for (i, (min, max)) in min.into_iter().zip(max).enumerate() {
+            let (near, far) = if self.direction[i] == 0.0 {
+                if self.origin[i] < min || self.origin[i] > max {
+                    return (f32::INFINITY, f32::NEG_INFINITY, i);
+                }
+                (f32::NEG_INFINITY, f32::INFINITY)
+            } else {
+                let a = (min - self.origin[i]) * self.inverse[i];
+                let b = (max - self.origin[i]) * self.inverse[i];
+                (a.min(b), a.max(b))
+            };
+            if near > enter {
+                enter = near;
+                axis = i;
+            }
+            exit = exit.min(far);
+        }
+        (enter, exit, axis)
+    }
+    /// Where the ray enters the part of a cube the clip plane doesn't hide, given where it
+    /// enters the whole cube. None if it misses that part.
+    fn visible(
+        &self,
+        min: [f32; 3],
+        size: f32,
+        (distance, face): (f32, Face),
+    ) -> Option<(f32, Face)> {
+        let Some(clip) = self.clip else {
+            return Some((distance, face));
+        };
+        if clip.hides(min) {
+            return None;
+        }
+        if !clip.cuts(min, size) {
+            return Some((distance, face));
+        }
+        let mut max = min.map(|min| min + size);
+        max[clip.axis] = clip.position;
+        let (enter, exit, axis) = self.slab_box(min, max);
+        let enter = enter.max(distance);
+        if enter > exit {
+            return None;
+        }
+        // the cap of the cut is the face towards the hidden side
+        let face = if enter > distance {
+            Face::entered(axis, self.direction[axis])
+        } else {
+            face
+        };
+        Some((enter, face))
+    }
+}
+
+impl VloxData {
+    /// Walks the octree along a ray and returns the first non-void vlox it enters.
+    ///
+    /// Whole void subtrees are skipped at once and children are visited front to back,
+    /// so the cost depends on the detail along the ray rather than the size of the data.
+    /// Vloxes stored deeper than `max_depth` count as hit if any part of them is non-void.
+    /// Everything `clip` hides is passed through.
+    pub fn raycast(
+        &self,
+        origin: (f32, f32, f32),
+        direction: (f32, f32, f32),
+        max_depth: u8,
+        clip: Option<&ClipPlane>,
+    ) -> Option<RaycastHit> {
+        let length =
+            (direction.0 * direction.0 + direction.1 * direction.1 + direction.2 * direction.2)
+                .sqrt();
+        if length == 0.0 || !length.is_finite() {
+            return None;
+        }
+        let direction = [
+            direction.0 / length,
+            direction.1 / length,
+            direction.2 / length,
+        ];
+        let ray = Ray {
+            origin: [origin.0, origin.1, origin.2],
+            direction,
+            inverse: direction.map(|d| 1.0 / d),
+            clip: clip.copied(),
+        };
+
+        let half = self.size * 0.5;
+        let min = [-half, -half, -half];
+        let (enter, exit, axis) = ray.slab(min, self.size);
+        if enter > exit || exit < 0.0 {
+            return None;
+        }
+        let face = Face::entered(axis, ray.direction[axis]);
+        self.root.raycast(
+            &ray,
+            min,
+            self.size,
+            (0, 0, 0, 0),
+            (enter.max(0.0), face),
+            max_depth,
+        )
+    }
+}
+
+impl Vlox {
+    fn raycast(
+        &self,
+        ray: &Ray,
+        min: [f32; 3],
+        size: f32,
+        (x, y, z, depth): (u128, u128, u128, u8),
+        (distance, face): (f32, Face),
+        max_depth: u8,
+    ) -> Option<RaycastHit> {
+        if ray.clip.is_some_and(|clip| clip.hides(min)) {
+            return None;
+        }
+        let hit = |value| {
+            let (distance, face) = ray.visible(min, size, (distance, face))?;
+            Some(RaycastHit {
+                x,
+                y,
+                z,
+                depth,
+                value,
+                face,
+                distance,
+            })
+        };
+        if self.children.is_empty() {
+            return if self.value != VOID {
+                hit(self.value)
+            } else {
+                None
+            };
+        }
+        if depth >= max_depth {
+            return if self.any_leaf_below(&|value| value != VOID) {
+                hit(self.summary.material)
+            } else {
+                None
+            };
+        }
+
+        // children the ray passes through, nearest first
+        let half = size * 0.5;
+        let mut children = vec![];
+        for i in 0..8_usize {
+            let (cx, cy, cz) = ((i >> 2) & 1, (i >> 1) & 1, i & 1);
+            let child_min = [
+                min[0] + cx as f32 * half,
+                min[1] + cy as f32 * half,
+                min[2] + cz as f32 * half,
+            ];
+            let (enter, exit, axis) = ray.slab(child_min, half);
+            let enter = enter.max(distance);
+            if enter <= exit {
+                children.push((enter, axis, i, child_min, (cx, cy, cz)));
+            }
+        }
+        children.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        for (enter, axis, i, child_min, (cx, cy, cz)) in children {
+            // the ray starts inside the child, so it keeps the face it entered the parent by
+            let face = if enter > distance {
+                Face::entered(axis, ray.direction[axis])
+            } else {
+                face
+            };
+            let key = (
+                x * 2 + cx as u128,
+                y * 2 + cy as u128,
+                z * 2 + cz as u128,
+                depth + 1,
+            );
+            let hit = match &self.children[i] {
+                Some(child) => child.raycast(ray, child_min, half, key, (enter, face), max_depth),
+                // children that were never created have this vlox's value
+                None if self.value != VOID => {
+                    ray.visible(child_min, half, (enter, face))
+                        .map(|(distance, face)| RaycastHit {
+                            x: key.0,
+                            y: key.1,
+                            z: key.2,
+                            depth: key.3,
+                            value: self.value,
+                            face,
+                            distance,
+                        })
+                }
+                None => None,
+            };
+            if hit.is_some() {
+                return hit;
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raycast_hits_nearest_vlox_at_its_depth() {
+        let mut data = VloxData::new(2);
+        data.set(0, 0, 0, 2, 1);
+        data.set(3, 0, 0, 2, 2);
+        // detail deeper than the rest
+        data.set(3, 1, 1, 3, 3);
+
+        // along +x through the bottom row, from outside the data
+        let hit = data
+            .raycast((-5.0, -1.75, -1.75), (1.0, 0.0, 0.0), 8, None)
+            .unwrap();
+        assert_eq!((0, 0, 0, 2, 1), (hit.x, hit.y, hit.z, hit.depth, hit.value));
+        assert_eq!(Face::NegX, hit.face);
+        assert_eq!(3.0, hit.distance);
+
+        // starting past the first vlox, looking back along -x
+        let hit = data
+            .raycast((1.5, -1.75, -1.75), (-1.0, 0.0, 0.0), 8, None)
+            .unwrap();
+        assert_eq!((3, 0, 0, 2, 2), (hit.x, hit.y, hit.z, hit.depth, hit.value));
+
+        let hit = data
+            .raycast((0.5, -1.75, -1.75), (-1.0, 0.0, 0.0), 8, None)
+            .unwrap();
+        assert_eq!((0, 0, 0, 1), (hit.x, hit.y, hit.z, hit.value));
+        assert_eq!(Face::PosX, hit.face);
+        assert_eq!(1.5, hit.distance);
+
+        // down onto the small vlox
+        let hit = data
+            .raycast((-0.25, 5.0, -1.25), (0.0, -1.0, 0.0), 8, None)
+            .unwrap();
+        assert_eq!((3, 1, 1, 3, 3), (hit.x, hit.y, hit.z, hit.depth, hit.value));
+        assert_eq!(Face::PosY, hit.face);
+        assert_eq!(6.0, hit.distance);
+
+        // which is only part of its parent at a shallower max depth
+        let hit = data
+            .raycast((-0.25, 5.0, -1.25), (0.0, -1.0, 0.0), 1, None)
+            .unwrap();
+        assert_eq!((0, 0, 0, 1), (hit.x, hit.y, hit.z, hit.depth));
+        assert_eq!(5.0, hit.distance);
+
+        assert_eq!(
+            None,
+            data.raycast((-5.0, 1.5, 1.5), (1.0, 0.0, 0.0), 8, None)
+        );
+        assert_eq!(
+            None,
+            data.raycast((-5.0, -1.5, -1.5), (-1.0, 0.0, 0.0), 8, None)
+        );
+    }
+
+    #[test]
+    fn hit_cells_come_from_the_hit_key() {
+        let mut data = VloxData::new(2);
+        // a big leaf at depth 1 and a small one at depth 3
+        data.set(0, 0, 0, 1, 1);
+        data.set(15, 0, 0, 4, 2);
+
+        // onto the top of the big leaf: the top layer of vloxes at depth 3, grazing the edge
+        // between two of them
+        let origin = (-1.5, 5.0, -1.75);
+        let hit = data.raycast(origin, (0.0, -1.0, 0.0), 8, None).unwrap();
+        let point = (origin.0, origin.1 - hit.distance, origin.2);
+        assert_eq!(((1, 3, 0), Some((1, 4, 0))), hit.cells(&data, point, 3));
+        // at the hit's own depth and above it
+        assert_eq!(((0, 0, 0), Some((0, 1, 0))), hit.cells(&data, point, 1));
+        assert_eq!(((0, 0, 0), None), hit.cells(&data, point, 0));
+
+        // into the side of the small leaf, at the edge of the data with nothing in front
+        let origin = (5.0, -1.9, -1.9);
+        let hit = data.raycast(origin, (-1.0, 0.0, 0.0), 8, None).unwrap();
+        let point = (origin.0 - hit.distance, origin.1, origin.2);
+        assert_eq!(((15, 0, 0), None), hit.cells(&data, point, 4));
+        assert_eq!(((3, 0, 0), None), hit.cells(&data, point, 2));
+
+        // and into its bottom, also at the edge
+        let origin = (1.9, -5.0, -1.9);
+        let hit = data.raycast(origin, (0.0, 1.0, 0.0), 8, None).unwrap();
+        let point = (origin.0, origin.1 + hit.distance, origin.2);
+        assert_eq!(((15, 0, 0), None), hit.cells(&data, point, 4));
+    }
+}
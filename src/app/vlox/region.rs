@@ -0,0 +1,338 @@
+use std::sync::Arc;
+
+use super::{CsgOp, Leaf, MaterialRule, SubVlox, Vlox, VloxData, VOID};
+
+/// How a clipboard is turned when it is pasted: 90° rotations and mirroring, as a permutation
+/// of the axes where each new axis may also be flipped
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Orientation {
+    /// Old axis each new axis comes from
+    axes: [usize; 3],
+    flip: [bool; 3],
+}
+impl Default for Orientation {
+    fn default() -> Self {
+        Self {
+            axes: [0, 1, 2],
+            flip: [false; 3],
+        }
+    }
+}
+impl Orientation {
+    /// Turned a further 90° about an axis (0, 1, 2 for x, y, z), counterclockwise looking
+    /// down the axis
+    pub fn rotated(self, axis: usize) -> Self {
+        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+        let mut rotation = Self::default();
+        rotation.axes[u] = v;
+        rotation.flip[u] = true;
+        rotation.axes[v] = u;
+        self.then(rotation)
+    }
+    /// Mirrored along an axis
+    pub fn mirrored(self, axis: usize) -> Self {
+        let mut mirror = Self::default();
+        mirror.flip[axis] = true;
+        self.then(mirror)
+    }
+    /// Size of a box of `extent` cells after turning it
+    pub fn extent(&self, extent: (u128, u128, u128)) -> (u128, u128, u128) {
+        let extent = [extent.0, extent.1, extent.2];
+        (
+            extent[self.axes[0]],
+            extent[self.axes[1]],
+            extent[self.axes[2]],
+        )
+    }
+
+    /// This orientation followed by another
+    fn then(self, other: Self) -> Self {
+        Self {
+            axes: other.axes.map(|axis| self.axes[axis]),
+            flip: [0, 1, 2].map(|i| other.flip[i] ^ self.flip[other.axes[i]]),
+        }
+    }
+    /// Where a cell of a box of `extent` cells ends up
+    fn cell(&self, (x, y, z): (u128, u128, u128), extent: (u128, u128, u128)) -> [u128; 3] {
+        let cell = [x, y, z];
+        let extent = [extent.0, extent.1, extent.2];
+        [0, 1, 2].map(|i| {
+            let value = cell[self.axes[i]];
+            if self.flip[i] {
+                extent[self.axes[i]] - 1 - value
+            } else {
+                value
+            }
+        })
+    }
+    /// Which child a child ends up as, children are a box of 2x2x2 cells
+    fn child(&self, i: usize) -> usize {
+        let i = i as u128;
+        let [x, y, z] = self.cell(((i >> 2) & 1, (i >> 1) & 1, i & 1), (2, 2, 2));
+        (x * 4 + y * 2 + z) as usize
+    }
+}
+
+/// A copied box of vloxes, with all their detail, kept as a standalone `VloxData`
+#[derive(Debug)]
+pub struct Clipboard {
+    data: VloxData,
+    /// Depth of the clipboard's cells in `data`
+    depth: u8,
+    extent: (u128, u128, u128),
+}
+impl Clipboard {
+    /// Size of the copied box in cells
+    pub fn extent(&self) -> (u128, u128, u128) {
+        self.extent
+    }
+
+    /// Where the cells that aren't all void end up when the clipboard is turned by
+    /// `orientation`, relative to its lowest corner
+    pub fn cells(&self, orientation: Orientation) -> Vec<(u128, u128, u128)> {
+        self.filled()
+            .map(|(cell, _)| {
+                let [x, y, z] = orientation.cell(cell, self.extent);
+                (x, y, z)
+            })
+            .collect()
+    }
+
+    /// The leaves to set to paste the clipboard turned by `orientation`, with its lowest corner
+    /// at `at`. Its cells become vloxes at `depth`; cells that are all void, and cells outside
+    /// the data, are left out.
+    pub fn leaves(
+        &self,
+        orientation: Orientation,
+        (x, y, z): (u128, u128, u128),
+        depth: u8,
+    ) -> Vec<Leaf> {
+        let num_vlox = 2_u128.pow(depth as u32);
+        let mut leaves = vec![];
+        for (cell, vlox) in self.filled() {
+            let [dx, dy, dz] = orientation.cell(cell, self.extent);
+            let cell = (x + dx, y + dy, z + dz);
+            if cell.0 >= num_vlox || cell.1 >= num_vlox || cell.2 >= num_vlox {
+                continue;
+            }
+            vlox.oriented(&orientation)
+                .leaves((cell.0, cell.1, cell.2, depth), &mut leaves);
+        }
+        leaves
+    }
+
+    /// Like `leaves`, but each cell is combined with the vlox already in `data` instead of
+    /// replacing it, as two standalone trees. Only the vloxes inside the pasted box change,
+    /// even when intersecting.
+    pub fn combined_leaves(
+        &self,
+        data: &VloxData,
+        orientation: Orientation,
+        (x, y, z): (u128, u128, u128),
+        depth: u8,
+        op: CsgOp,
+        rule: MaterialRule,
+    ) -> Vec<Leaf> {
+        let num_vlox = data.num_vlox(depth);
+        let mut leaves = vec![];
+        for cell in self.all_cells() {
+            let [dx, dy, dz] = orientation.cell(cell, self.extent);
+            let (cx, cy, cz) = (x + dx, y + dy, z + dz);
+            if cx >= num_vlox || cy >= num_vlox || cz >= num_vlox {
+                continue;
+            }
+            let clipped = VloxData {
+                size: data.vlox_size(num_vlox),
+                root: self
+                    .data
+                    .root
+                    .subtree(&self.data.xyz_to_path(cell.0, cell.1, cell.2, self.depth))
+                    .oriented(&orientation),
+            };
+            let mut combined = VloxData {
+                size: clipped.size,
+                root: data.root.subtree(&data.xyz_to_path(cx, cy, cz, depth)),
+            };
+            match op {
+                CsgOp::Union => combined.union(&clipped, (0, 0, 0), 0, rule),
+                CsgOp::Subtract => combined.subtract(&clipped, (0, 0, 0), 0),
+                CsgOp::Intersect => combined.intersect(&clipped, (0, 0, 0), 0, rule),
+                CsgOp::Xor => combined.xor(&clipped, (0, 0, 0), 0),
+            }
+            combined.root.leaves((cx, cy, cz, depth), &mut leaves);
+        }
+        leaves
+    }
+
+    fn all_cells(&self) -> impl Iterator<Item = (u128, u128, u128)> {
+        let (ex, ey, ez) = self.extent;
+        (0..ex).flat_map(move |x| (0..ey).flat_map(move |y| (0..ez).map(move |z| (x, y, z))))
+    }
+
+    /// The cells that aren't all void, with their contents
+    fn filled(&self) -> impl Iterator<Item = ((u128, u128, u128), Vlox)> + '_ {
+        self.all_cells().filter_map(|(x, y, z)| {
+            let path = self.data.xyz_to_path(x, y, z, self.depth);
+            let vlox = self.data.root.subtree(&path);
+            vlox.any_leaf_below(&|id| id != VOID)
+                .then_some(((x, y, z), vlox))
+        })
+    }
+}
+
+impl VloxData {
+    /// Copies the box of vloxes at `depth` from `min` to `max` inclusive
+    pub fn copy_region(
+        &self,
+        min: (u128, u128, u128),
+        max: (u128, u128, u128),
+        depth: u8,
+    ) -> Clipboard {
+        let extent = (max.0 - min.0 + 1, max.1 - min.1 + 1, max.2 - min.2 + 1);
+        let largest = extent.0.max(extent.1).max(extent.2);
+        let clipboard_depth = largest.next_power_of_two().trailing_zeros() as u8;
+
+        let mut data = VloxData::new(clipboard_depth);
+        for x in 0..extent.0 {
+            for y in 0..extent.1 {
+                for z in 0..extent.2 {
+                    let vlox = self.root.subtree(&self.xyz_to_path(
+                        min.0 + x,
+                        min.1 + y,
+                        min.2 + z,
+                        depth,
+                    ));
+                    let path = data.xyz_to_path(x, y, z, clipboard_depth);
+                    data.root.set_subtree(&path, vlox);
+                }
+            }
+        }
+        Clipboard {
+            data,
+            depth: clipboard_depth,
+            extent,
+        }
+    }
+}
+
+impl Vlox {
+    /// A copy of the vlox at the end of the path
+    pub(super) fn subtree(&self, path: &[SubVlox]) -> Vlox {
+        if path.is_empty() {
+            return self.clone();
+        }
+        match self.children.get(path[0] as usize) {
+            Some(Some(child)) => child.subtree(&path[1..]),
+            // past the stored detail, the vlox is a leaf with this vlox's value
+            _ => Vlox::uniform(self.value),
+        }
+    }
+    pub(super) fn set_subtree(&mut self, path: &[SubVlox], vlox: Vlox) {
+        if path.is_empty() {
+            *self = vlox;
+            return;
+        }
+        if self.children.is_empty() {
+            self.children = vec![None; 8];
+        }
+        let value = self.value;
+        let child =
+            self.children[path[0] as usize].get_or_insert_with(|| Arc::new(Vlox::new(value)));
+        Arc::make_mut(child).set_subtree(&path[1..], vlox);
+        self.summarise();
+    }
+    fn oriented(&self, orientation: &Orientation) -> Vlox {
+        let mut children = vec![None; self.children.len()];
+        for (i, child) in self.children.iter().enumerate() {
+            children[orientation.child(i)] = child
+                .as_ref()
+                .map(|child| Arc::new(child.oriented(orientation)));
+        }
+        // turning doesn't change what is inside
+        Vlox {
+            children,
+            ..self.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paste(
+        data: &mut VloxData,
+        clipboard: &Clipboard,
+        orientation: Orientation,
+        at: (u128, u128, u128),
+        depth: u8,
+    ) {
+        for (x, y, z, depth, value) in clipboard.leaves(orientation, at, depth) {
+            data.set(x, y, z, depth, value);
+        }
+    }
+
+    #[test]
+    fn orientations_turn_cells_and_compose() {
+        let extent = (2, 3, 4);
+        let quarter = Orientation::default().rotated(1);
+        assert_eq!((4, 3, 2), quarter.extent(extent));
+        // about y, +x turns towards -z
+        assert_eq!([0, 0, 1], quarter.cell((0, 0, 0), extent));
+        assert_eq!([3, 0, 1], quarter.cell((0, 0, 3), extent));
+
+        let full = quarter.rotated(1).rotated(1).rotated(1);
+        assert_eq!(Orientation::default(), full);
+        assert_eq!(
+            Orientation::default(),
+            Orientation::default().mirrored(2).mirrored(2)
+        );
+    }
+
+    #[test]
+    fn paste_keeps_detail_through_rotation_and_mirroring() {
+        let mut data = VloxData::new(2);
+        // a 2x1x1 bar at depth 2 with a finer notch in its +x end
+        data.set(0, 0, 0, 2, 1);
+        data.set(1, 0, 0, 2, 1);
+        data.set(3, 1, 1, 3, 2);
+        let clipboard = data.copy_region((0, 0, 0), (1, 0, 0), 2);
+        assert_eq!((2, 1, 1), clipboard.extent());
+
+        // straight copy, moved up
+        paste(&mut data, &clipboard, Orientation::default(), (0, 2, 0), 2);
+        assert_eq!(1, data.get(0, 2, 0, 2));
+        assert_eq!(2, data.get(3, 5, 1, 3));
+        assert_eq!(1, data.get(3, 5, 0, 3));
+
+        // mirrored along x the notch is at the low end
+        let mut mirrored = VloxData::new(2);
+        paste(
+            &mut mirrored,
+            &clipboard,
+            Orientation::default().mirrored(0),
+            (0, 0, 0),
+            2,
+        );
+        assert_eq!(2, mirrored.get(0, 1, 1, 3));
+        assert_eq!(1, mirrored.get(1, 1, 1, 3));
+
+        // turned about y the bar runs along z, and +x turning to -z puts the notch at its low end
+        let mut rotated = VloxData::new(2);
+        paste(
+            &mut rotated,
+            &clipboard,
+            Orientation::default().rotated(1),
+            (0, 0, 0),
+            2,
+        );
+        assert_eq!(1, rotated.get(0, 0, 0, 3));
+        assert_eq!(1, rotated.get(0, 0, 1, 2));
+        assert_eq!(VOID, rotated.get(1, 0, 0, 2));
+        let notch = (0..4)
+            .flat_map(|x| (0..4).flat_map(move |y| (0..4).map(move |z| (x, y, z))))
+            .filter(|&(x, y, z)| rotated.get(x, y, z, 3) == 2)
+            .collect::<Vec<_>>();
+        assert_eq!(vec![(1, 1, 0)], notch);
+    }
+}
@@ -0,0 +1,187 @@
+use std::sync::Arc;
+
+use super::{SubVlox, Vlox, VloxData};
+
+/// A `Vlox` that is stored in the tree, rather than implied by its parent
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct StoredNode {
+    pub x: u128,
+    pub y: u128,
+    pub z: u128,
+    pub depth: u8,
+    /// Whether it has no stored children
+    pub leaf: bool,
+}
+
+impl VloxData {
+    /// Subdivides every leaf inside the vlox at `region_depth` until all of them are at
+    /// `depth`, without changing what they look like. Returns the number of nodes added, so
+    /// refining one level splits a leaf into eight children with its material.
+    pub fn refine_region(
+        &mut self,
+        (x, y, z): (u128, u128, u128),
+        region_depth: u8,
+        depth: u8,
+    ) -> usize {
+        let path = self.xyz_to_path(x, y, z, region_depth);
+        self.root
+            .refine_at(&path, depth.saturating_sub(region_depth))
+    }
+    /// The stored nodes inside the vlox at `depth`, down to `max_depth`, parents first
+    pub fn stored_nodes(
+        &self,
+        (x, y, z): (u128, u128, u128),
+        depth: u8,
+        max_depth: u8,
+    ) -> Vec<StoredNode> {
+        let mut vlox = &self.root;
+        for i in self.xyz_to_path(x, y, z, depth) {
+            match vlox.children.get(i as usize) {
+                Some(Some(child)) => vlox = child,
+                _ => return vec![],
+            }
+        }
+        let mut nodes = vec![];
+        vlox.walk((x, y, z, depth), max_depth, &mut |node| {
+            nodes.push(*node);
+            true
+        });
+        nodes
+    }
+    /// Calls `visit` with every stored node down to `max_depth`, parents first, without
+    /// collecting them. The children of nodes it returns false for are skipped, so whole
+    /// subtrees can be culled.
+    pub fn walk_stored_nodes(&self, max_depth: u8, visit: &mut impl FnMut(&StoredNode) -> bool) {
+        self.root.walk((0, 0, 0, 0), max_depth, visit);
+    }
+}
+
+impl Vlox {
+    fn refine_at(&mut self, path: &[SubVlox], levels: u8) -> usize {
+        let Some((&first, rest)) = path.split_first() else {
+            return self.refine(levels);
+        };
+        if self.children.is_empty() {
+            self.children = vec![None; 8];
+        }
+        let value = self.value;
+        let child = &mut self.children[first as usize];
+        let created = child.is_none() as usize;
+        let child = child.get_or_insert_with(|| Arc::new(Vlox::uniform(value)));
+        let added = Arc::make_mut(child).refine_at(rest, levels);
+        self.summarise();
+        created + added
+    }
+    fn refine(&mut self, levels: u8) -> usize {
+        if levels == 0 {
+            return 0;
+        }
+        if self.children.is_empty() {
+            self.children = vec![None; 8];
+        }
+        let value = self.value;
+        let mut added = 0;
+        for child in self.children.iter_mut() {
+            if child.is_none() {
+                added += 1;
+            }
+            let child = child.get_or_insert_with(|| Arc::new(Vlox::uniform(value)));
+            added += Arc::make_mut(child).refine(levels - 1);
+        }
+        self.summarise();
+        added
+    }
+    fn walk(
+        &self,
+        (x, y, z, depth): (u128, u128, u128, u8),
+        max_depth: u8,
+        visit: &mut impl FnMut(&StoredNode) -> bool,
+    ) {
+        let stored = self.children.iter().flatten().count();
+        let node = StoredNode {
+            x,
+            y,
+            z,
+            depth,
+            leaf: stored == 0,
+        };
+        if !visit(&node) || depth >= max_depth {
+            return;
+        }
+        for (i, child) in self.children.iter().enumerate() {
+            let Some(child) = child else {
+                continue;
+            };
+            let i = i as u128;
+            let key = (
+                x * 2 + ((i >> 2) & 1),
+                y * 2 + ((i >> 1) & 1),
+                z * 2 + (i & 1),
+                depth + 1,
+            );
+            child.walk(key, max_depth, visit);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subdivide_and_refine_keep_the_look() {
+        let mut data = VloxData::new(2);
+        data.set(0, 0, 0, 1, 1);
+        data.set(1, 1, 1, 1, 2);
+        let before = data.clone();
+
+        assert_eq!(8, data.refine_region((0, 0, 0), 1, 2));
+        assert_eq!(0, data.refine_region((0, 0, 0), 1, 2));
+        assert_eq!(8, data.stored_nodes((0, 0, 0), 1, 2).len() - 1);
+        // refining inside a vlox that isn't stored creates the path to it
+        assert_eq!(1 + 8 + 64, data.refine_region((1, 0, 0), 1, 3));
+        assert_eq!(1 + 8 + 64, data.stored_nodes((1, 0, 0), 1, 3).len());
+        assert!(data
+            .stored_nodes((1, 0, 0), 1, 3)
+            .iter()
+            .all(|node| node.leaf == (node.depth == 3)));
+        for x in 0..8 {
+            for y in 0..8 {
+                for z in 0..8 {
+                    assert_eq!(before.get(x, y, z, 3), data.get(x, y, z, 3));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn walks_skip_culled_subtrees() {
+        let mut data = VloxData::new(2);
+        data.set(0, 0, 0, 3, 1);
+        data.set(7, 7, 7, 3, 1);
+
+        let mut visited = vec![];
+        data.walk_stored_nodes(u8::MAX, &mut |node| {
+            visited.push((node.x, node.y, node.z, node.depth));
+            // cull everything inside the far corner
+            node.x == 0
+        });
+        assert_eq!(
+            vec![
+                (0, 0, 0, 0),
+                (0, 0, 0, 1),
+                (0, 0, 0, 2),
+                (0, 0, 0, 3),
+                (1, 1, 1, 1)
+            ],
+            visited
+        );
+
+        let mut count = 0;
+        data.walk_stored_nodes(1, &mut |_| {
+            count += 1;
+            true
+        });
+        assert_eq!(3, count);
+    }
+}
@@ -0,0 +1,119 @@
+use super::{MaterialId, SubVlox, Vlox, VloxData, VOID};
+
+/// What is inside a vlox, kept on every node so coarse queries don't have to visit its
+/// leaves. Subdivided vloxes are summarised from their children's summaries, like the levels
+/// of a mipmap.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct VloxSummary {
+    /// The non-void material filling the most of the vlox, VOID if it is empty
+    pub material: MaterialId,
+    /// Fraction of the vlox that isn't void
+    pub occupancy: f32,
+}
+impl VloxSummary {
+    pub fn of(value: MaterialId) -> Self {
+        Self {
+            material: value,
+            occupancy: if value == VOID { 0.0 } else { 1.0 },
+        }
+    }
+    /// The material that stands for the whole vlox: void unless at least half of it is filled
+    pub fn value(&self) -> MaterialId {
+        if self.occupancy >= 0.5 {
+            self.material
+        } else {
+            VOID
+        }
+    }
+
+    /// Summary of a vlox made of equal parts with these summaries
+    fn mix(parts: &[VloxSummary]) -> Self {
+        // volume of each non-void material, in the order they first appear
+        let mut volumes: Vec<(MaterialId, f32)> = vec![];
+        for part in parts.iter().filter(|part| part.material != VOID) {
+            match volumes.iter_mut().find(|(id, _)| *id == part.material) {
+                Some((_, volume)) => *volume += part.occupancy,
+                None => volumes.push((part.material, part.occupancy)),
+            }
+        }
+        let material = volumes
+            .iter()
+            .fold(
+                None,
+                |best: Option<(MaterialId, f32)>, &(id, volume)| match best {
+                    Some((_, most)) if most >= volume => best,
+                    _ => Some((id, volume)),
+                },
+            )
+            .map_or(VOID, |(id, _)| id);
+        Self {
+            material,
+            occupancy: parts.iter().map(|part| part.occupancy).sum::<f32>() / parts.len() as f32,
+        }
+    }
+}
+
+impl VloxData {
+    /// Summary of the vlox at `depth`, however deeply it is subdivided
+    pub fn summary(&self, x: u128, y: u128, z: u128, depth: u8) -> VloxSummary {
+        self.root.summary_at(&self.xyz_to_path(x, y, z, depth))
+    }
+}
+
+impl Vlox {
+    /// Updates the summary from the children, which must already be summarised
+    pub(super) fn summarise(&mut self) {
+        let fill = VloxSummary::of(self.value);
+        self.summary = if self.children.is_empty() {
+            fill
+        } else {
+            let parts: Vec<VloxSummary> = self
+                .children
+                .iter()
+                .map(|child| child.as_ref().map_or(fill, |child| child.summary))
+                .collect();
+            VloxSummary::mix(&parts)
+        };
+    }
+    fn summary_at(&self, path: &[SubVlox]) -> VloxSummary {
+        if path.is_empty() {
+            return self.summary;
+        }
+        match self.children.get(path[0] as usize) {
+            Some(Some(child)) => child.summary_at(&path[1..]),
+            _ => VloxSummary::of(self.value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parents_summarise_their_children() {
+        let mut data = VloxData::new(2);
+        data.set(0, 0, 0, 1, 1);
+        // carve a quarter out, and put a different material in another quarter
+        data.set(0, 0, 0, 2, VOID);
+        data.set(0, 0, 1, 2, VOID);
+        data.set(1, 1, 0, 2, 2);
+        data.set(1, 1, 1, 2, 2);
+
+        let cell = data.summary(0, 0, 0, 1);
+        assert_eq!(1, cell.material);
+        assert_eq!(0.75, cell.occupancy);
+        assert_eq!(1, data.get(0, 0, 0, 1));
+        let root = data.summary(0, 0, 0, 0);
+        assert_eq!(1, root.material);
+        assert_eq!(0.75 / 8.0, root.occupancy);
+        // mostly empty, so void at the coarsest depth
+        assert_eq!(VOID, data.get(0, 0, 0, 0));
+
+        data.set(1, 0, 0, 2, 2);
+        data.set(1, 0, 1, 2, 2);
+        assert_eq!(2, data.get(0, 0, 0, 1));
+        data.set(0, 0, 0, 1, VOID);
+        assert_eq!(VloxSummary::of(VOID), data.summary(0, 0, 0, 0));
+    }
+}